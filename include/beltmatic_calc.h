@@ -0,0 +1,25 @@
+#ifndef BELTMATIC_CALC_H
+#define BELTMATIC_CALC_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Solves `target` combining the numbers in `1..max_number` with the CSV list of operations
+ * `ops`, returning the solution as a JSON document with the fields of the `json` format, or
+ * `{"error": ...}` when the arguments are invalid, e.g. `max_number` is 0 or above 1000. The
+ * document must be released with `beltmatic_free`.
+ */
+char *beltmatic_solve(int64_t target, uint32_t max_number, const char *ops);
+
+/* Releases a document returned by `beltmatic_solve`. NULL is ignored. */
+void beltmatic_free(char *document);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,74 @@
+use beltmatic_calc::{Expression, Rates};
+
+use crate::{
+    failure::{fail, Failure},
+    throughput::rates,
+    AnalyzeArgs,
+};
+
+// The machines and extractors of the expression processing items at `rate`, described by the
+// subexpression they produce, deepest first.
+fn slowest(expression: &Expression, rates: &Rates, rate: f64, out: &mut Vec<String>) {
+    match expression {
+        Expression::Number(value) => {
+            if rates.capacity(expression) == rate {
+                out.push(format!("the extractor of {}", value));
+            }
+            return;
+        }
+        Expression::Binary(_, left, right) => {
+            slowest(left, rates, rate, out);
+            slowest(right, rates, rate, out);
+        }
+        Expression::Unary(_, operand) => slowest(operand, rates, rate, out),
+    }
+    if rates.capacity(expression) == rate {
+        let operation = match expression {
+            Expression::Binary(operation, _, _) => operation.to_string(),
+            Expression::Unary(operation, _) => operation.to_string(),
+            Expression::Number(_) => unreachable!(),
+        };
+        out.push(format!("the {} machine of {}", operation, expression));
+    }
+}
+
+pub(crate) fn run(args: &AnalyzeArgs) {
+    let expression: Expression = match args.expression.parse() {
+        Ok(expression) => expression,
+        Err(e) => {
+            fail(
+                Failure::Arguments,
+                format!("unable to parse {}: {}", args.expression, e),
+            );
+        }
+    };
+    let rates = rates(args.extractor_rate, &args.op_rate);
+    let throughput = rates.throughput(&expression);
+    println!("expression: {}", expression);
+    println!("throughput: {} items/s", throughput);
+    let mut slow = vec![];
+    slowest(&expression, &rates, throughput, &mut slow);
+    for description in slow {
+        println!("bottleneck: {} at {} items/s", description, throughput);
+    }
+    match rates.bottlenecks(&expression) {
+        (branches, Some(limit)) => {
+            // The copies of a branch share its load, and their outputs merge onto one belt.
+            let copies = (limit / throughput).ceil() as u64;
+            let branches: Vec<String> = branches.iter().map(|branch| branch.to_string()).collect();
+            let branches = match branches.as_slice() {
+                [branch] => branch.clone(),
+                [rest @ .., last] => format!("each of {} and {}", rest.join(", "), last),
+                [] => unreachable!(),
+            };
+            println!(
+                "suggestion: build {} copies of {} to deliver {} items/s",
+                copies, branches, limit
+            );
+        }
+        (_, None) => println!(
+            "suggestion: build copies of the whole expression, each delivering {} items/s",
+            throughput
+        ),
+    }
+}
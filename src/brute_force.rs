@@ -0,0 +1,666 @@
+use rayon::prelude::*;
+use std::{
+    cell::RefCell,
+    collections::{hash_map::Entry, HashMap, HashSet},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+// Leaf assignments explored between two progress reports.
+const PROGRESS_INTERVAL: u64 = 4096;
+
+// Operator assignments given to each thread between two opportunities to save a checkpoint.
+const CHUNK_PER_THREAD: usize = 16;
+
+use crate::{
+    limits::{Usage, Uses},
+    progress::report_solved,
+    Checkpoint, EvalError, Expression, Interruption, Limits, Operation, Progress, Solution,
+    Solutions, SolverConfig, UnaryOperation,
+};
+
+// The parenthesisations of a size are stored in a flat arena, every node referring to its
+// operands by their index. Operands always come before the nodes using them, so evaluating the
+// arena in order computes every shared subtree once per assignment of the numbers.
+#[derive(Clone, Copy)]
+enum Node {
+    // The index of the number.
+    Number(usize),
+    // The index of the operation, between the numbers `operation` and `operation + 1`.
+    Binary {
+        left: usize,
+        right: usize,
+        operation: usize,
+    },
+    // Unary operations are part of the shape of the parenthesisations, so that their cost is
+    // known when the parenthesisations are built.
+    Unary {
+        operand: usize,
+        operation: UnaryOperation,
+    },
+}
+
+// A parenthesisation, as the index of its root, with the cost and the uses of its unary
+// operations.
+type Alternative = (usize, u64, Uses);
+
+struct Composed {
+    ints: Vec<i64>,
+    ops: Vec<Operation>,
+    nodes: Vec<Node>,
+    values: Vec<Result<i64, EvalError>>,
+    alternatives: Vec<Alternative>,
+    // affected[i] lists, in order, the nodes whose first number is at most the `i`-th, which are
+    // the only ones to evaluate again after replacing the numbers up to the `i`-th.
+    affected: Vec<Vec<usize>>,
+    // Nodes left out of the current operations, with the nodes using them.
+    redundant: Vec<bool>,
+}
+
+impl Composed {
+    // Evaluates the nodes affected by replacing the numbers up to the `changed`-th, or every node
+    // with the last number, after replacing operations.
+    // Wasteful operations fail, like those overflowing, so that the expressions using them are
+    // skipped.
+    fn evaluate(&mut self, changed: usize, config: &SolverConfig) {
+        for &i in self.affected[changed].iter() {
+            if self.redundant[i] {
+                continue;
+            }
+            self.values[i] = match self.nodes[i] {
+                Node::Number(index) => Ok(self.ints[index]),
+                Node::Binary {
+                    left,
+                    right,
+                    operation,
+                } => match (self.values[left], self.values[right]) {
+                    (Ok(l), Ok(r)) => {
+                        let operation = self.ops[operation];
+                        let composite = self.is_composite(left) || self.is_composite(right);
+                        if config.is_wasteful(operation, l, r, composite) {
+                            Err(EvalError::Wasteful)
+                        } else {
+                            operation.apply(l, r, config.semantics)
+                        }
+                    }
+                    (Err(e), _) | (_, Err(e)) => Err(e),
+                },
+                Node::Unary { operand, operation } => {
+                    self.values[operand].and_then(|v| operation.apply(v, config.semantics))
+                }
+            };
+        }
+    }
+
+    // Whether the node combines several numbers.
+    fn is_composite(&self, node: usize) -> bool {
+        match self.nodes[node] {
+            Node::Number(_) => false,
+            Node::Binary { .. } => true,
+            Node::Unary { operand, .. } => self.is_composite(operand),
+        }
+    }
+
+    // With deduplication, chains of the same commutative operation are only kept in their
+    // left-leaning parenthesisation, e.g. `((a+b)+c)` but not `(a+(b+c))`, as all the
+    // parenthesisations of a chain have the same canonical form. The left-leaning one uses the
+    // same operations, so it is found with the same assignment. Operations fed by one they are
+    // forbidden to are left out too.
+    fn prune(&mut self, config: &SolverConfig) {
+        let dedupe = config.deduplicates();
+        for i in 0..self.nodes.len() {
+            self.redundant[i] = match self.nodes[i] {
+                Node::Number(_) => false,
+                Node::Binary {
+                    left,
+                    right,
+                    operation,
+                } => {
+                    let operation = self.ops[operation];
+                    let root = |node: usize| match self.nodes[node] {
+                        Node::Binary { operation, .. } => Some(self.ops[operation]),
+                        _ => None,
+                    };
+                    let chained = root(right) == Some(operation);
+                    let forbidden = [left, right].iter().any(|operand| {
+                        root(*operand)
+                            .is_some_and(|producer| config.limits.forbids(producer, operation))
+                    });
+                    self.redundant[left]
+                        || self.redundant[right]
+                        || dedupe && operation.is_commutative() && chained
+                        || forbidden
+                }
+                Node::Unary { operand, .. } => self.redundant[operand],
+            };
+        }
+    }
+
+    fn expression(&self, node: usize) -> Expression {
+        match self.nodes[node] {
+            Node::Number(index) => Expression::Number(self.ints[index]),
+            Node::Binary {
+                left,
+                right,
+                operation,
+            } => Expression::binary(
+                self.ops[operation],
+                self.expression(left),
+                self.expression(right),
+            ),
+            Node::Unary { operand, operation } => {
+                Expression::unary(operation, self.expression(operand))
+            }
+        }
+    }
+}
+
+fn make_options(size: usize, config: &SolverConfig) -> Composed {
+    let usage = Usage::new(config);
+    let unary: Vec<(UnaryOperation, u64, Uses)> = config
+        .operations
+        .unary_operations()
+        .iter()
+        .enumerate()
+        .map(|(i, operation)| {
+            (
+                *operation,
+                config.objective.unary_cost(*operation),
+                usage.unary(i),
+            )
+        })
+        .collect();
+    let mut parenthesisations = Parenthesisations {
+        limits: &config.limits,
+        unary: &unary,
+        usage: &usage,
+        nodes: vec![],
+        built: HashMap::new(),
+    };
+    let alternatives = parenthesisations.calculate(0, size);
+    let nodes = parenthesisations.nodes;
+    let mut first: Vec<usize> = Vec::with_capacity(nodes.len());
+    for node in nodes.iter() {
+        first.push(match *node {
+            Node::Number(index) => index,
+            Node::Binary { left, .. } => first[left],
+            Node::Unary { operand, .. } => first[operand],
+        });
+    }
+    let affected = (0..size)
+        .map(|changed| (0..nodes.len()).filter(|i| first[*i] <= changed).collect())
+        .collect();
+    Composed {
+        ints: vec![0; size],
+        ops: vec![Operation::ADD; size - 1],
+        values: vec![Ok(0); nodes.len()],
+        redundant: vec![false; nodes.len()],
+        nodes,
+        alternatives,
+        affected,
+    }
+}
+
+// The number of parenthesisations `make_options` builds for the size, counted by their uses
+// of the limited operations without building them.
+fn count_shapes(size: usize, config: &SolverConfig) -> u64 {
+    let usage = &Usage::new(config);
+    let unary: Vec<Uses> = (0..config.operations.unary_operations().len())
+        .map(|i| usage.unary(i))
+        .collect();
+    let add = |counts: &mut HashMap<Uses, u64>, uses: Uses, count: u64| {
+        let total = counts.entry(uses).or_default();
+        *total = total.saturating_add(count);
+    };
+    // counts[n] maps the uses of the parenthesisations of `n` numbers to how many have them.
+    let mut counts: Vec<HashMap<Uses, u64>> = vec![HashMap::new()];
+    for n in 1..=size {
+        let mut count = HashMap::new();
+        if n == 1 {
+            add(&mut count, 0, 1);
+        }
+        for split in config.limits.splits(n) {
+            for (left_uses, left_count) in counts[split].iter() {
+                for (right_uses, right_count) in counts[n - split].iter() {
+                    if let Some(uses) = usage
+                        .combine(*left_uses, *right_uses)
+                        .and_then(|uses| usage.deepen(uses))
+                    {
+                        add(&mut count, uses, left_count.saturating_mul(*right_count));
+                    }
+                }
+            }
+        }
+        let wrapped: Vec<(Uses, u64)> = count
+            .iter()
+            .flat_map(|(&uses, &count)| {
+                unary.iter().filter_map(move |&unary_uses| {
+                    usage
+                        .combine(uses, unary_uses)
+                        .and_then(|uses| usage.deepen(uses))
+                        .map(|uses| (uses, count))
+                })
+            })
+            .collect();
+        for (uses, wrapped_count) in wrapped {
+            add(&mut count, uses, wrapped_count);
+        }
+        counts.push(count);
+    }
+    counts[size]
+        .values()
+        .fold(0, |total, count| total.saturating_add(*count))
+}
+
+// The parenthesisations of every range of leaves are built once and shared by all the larger
+// ranges containing it. Every parenthesisation, leaves included, is also wrapped once in each
+// unary operation.
+struct Parenthesisations<'a> {
+    limits: &'a Limits,
+    unary: &'a [(UnaryOperation, u64, Uses)],
+    usage: &'a Usage,
+    nodes: Vec<Node>,
+    built: HashMap<(usize, usize), Vec<Alternative>>,
+}
+
+impl Parenthesisations<'_> {
+    fn push(&mut self, node: Node) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    fn calculate(&mut self, left: usize, right: usize) -> Vec<Alternative> {
+        if let Some(result) = self.built.get(&(left, right)) {
+            return result.clone();
+        }
+        let mut result: Vec<Alternative> = Vec::new();
+        if left + 1 == right {
+            result.push((self.push(Node::Number(left)), 0, 0));
+        }
+        for i in self.limits.splits(right - left).map(|split| left + split) {
+            let left_combinations = self.calculate(left, i);
+            let right_combinations = self.calculate(i, right);
+            for (left_node, left_cost, left_uses) in left_combinations.iter() {
+                for (right_node, right_cost, right_uses) in right_combinations.iter() {
+                    let uses = match self
+                        .usage
+                        .combine(*left_uses, *right_uses)
+                        .and_then(|uses| self.usage.deepen(uses))
+                    {
+                        Some(uses) => uses,
+                        None => continue,
+                    };
+                    let node = self.push(Node::Binary {
+                        left: *left_node,
+                        right: *right_node,
+                        operation: i - 1,
+                    });
+                    result.push((node, left_cost + right_cost, uses))
+                }
+            }
+        }
+        let result = self.wrap(result);
+        self.built.insert((left, right), result.clone());
+        result
+    }
+
+    fn wrap(&mut self, alternatives: Vec<Alternative>) -> Vec<Alternative> {
+        if self.unary.is_empty() {
+            return alternatives;
+        }
+        let mut result = alternatives.clone();
+        for (node, cost, uses) in alternatives {
+            for &(operation, unary_cost, unary_uses) in self.unary {
+                if let Some(uses) = self
+                    .usage
+                    .combine(uses, unary_uses)
+                    .and_then(|uses| self.usage.deepen(uses))
+                {
+                    let unary = self.push(Node::Unary {
+                        operand: node,
+                        operation,
+                    });
+                    result.push((unary, cost + unary_cost, uses));
+                }
+            }
+        }
+        result
+    }
+}
+
+type Found = (HashMap<i64, Solution>, u64);
+
+fn merge_found(left: Found, right: Found, limit: Option<usize>) -> Found {
+    let (mut left_found, left_overflows) = left;
+    let (right_found, right_overflows) = right;
+    for (v, solution) in right_found {
+        match left_found.entry(v) {
+            Entry::Vacant(entry) => {
+                entry.insert(solution);
+            }
+            Entry::Occupied(mut entry) => {
+                entry.get_mut().merge(solution, limit);
+            }
+        }
+    }
+    (left_found, left_overflows + right_overflows)
+}
+
+pub(crate) fn search(
+    config: &SolverConfig,
+    progress: &dyn Progress,
+    mut state: Checkpoint,
+) -> Solutions {
+    let operation_dictionary = &config.operations;
+    let numbers = &config.numbers;
+    let maximum_size = config.max_size;
+    let targets = config.target_set();
+    let mut max_alternatives = if config.keeps_all_alternatives() {
+        None
+    } else {
+        config.max_alternatives
+    };
+    let usage = Usage::new(config);
+    let limited = config.limits.is_limited();
+    let numbers_weighted = config.objective.has_number_costs();
+    let pruned = config.deduplicates() || !config.limits.forbidden_adjacent().is_empty();
+    let sources_limited = !config.limits.required().is_empty()
+        || numbers
+            .iter()
+            .any(|n| config.limits.max_source_uses(*n).is_some());
+    let mut last_checkpoint = Instant::now();
+    let mut reported = HashSet::new();
+    let deadline = config.deadline();
+    let expired = || deadline.expired();
+    let timed_out = AtomicBool::new(false);
+    let mut truncated = None;
+    let mut degraded = false;
+    let unsaved = RefCell::new(None);
+    // The checkpoint of an interrupted search is saved where it can resume from, and not once
+    // more at the end.
+    let checkpoint = |state: &Checkpoint| {
+        if let Some(policy) = &config.checkpoint {
+            save(state, &policy.path, &unsaved);
+        }
+    };
+    while state.size <= maximum_size {
+        let size = state.size;
+        if expired() {
+            truncated = Some((size, deadline.interruption()));
+            checkpoint(&state);
+            break;
+        }
+        let operations_count = operation_dictionary.operations().len();
+        let assignments = operations_count.saturating_pow(size as u32 - 1);
+        let shapes = count_shapes(size, config);
+        let per_assignment = (numbers.len() as u64)
+            .saturating_pow(size as u32)
+            .saturating_mul(shapes);
+        progress.size_started(size, (assignments as u64).saturating_mul(per_assignment));
+        progress.explored((state.next_assignment as u64).saturating_mul(per_assignment));
+        let discovered: Mutex<HashSet<i64>> = Mutex::new(
+            state
+                .found
+                .keys()
+                .filter(|v| !state.solutions.contains_key(v))
+                .copied()
+                .collect(),
+        );
+        let chunk = rayon::current_num_threads() * CHUNK_PER_THREAD;
+        // Operator assignments are processed in chunks, between which the state can be saved.
+        // Within a chunk every assignment is explored independently on a thread-local copy of
+        // the parenthesisations, and the per-assignment results are merged in assignment order.
+        while truncated.is_none() && state.next_assignment < assignments {
+            let end = assignments.min(state.next_assignment + chunk);
+            let dictionary = &state.solutions;
+            let (found, chunk_overflows) = (state.next_assignment..end)
+                .into_par_iter()
+                .map_init(
+                    || make_options(size, config),
+                    |composed, assignment| {
+                        let mut found: HashMap<i64, Solution> = HashMap::new();
+                        let mut overflows = 0;
+                        // Once the timeout expires the assignments left are skipped and the
+                        // ones being explored stop at their next progress report.
+                        if timed_out.load(Ordering::Relaxed) || expired() {
+                            timed_out.store(true, Ordering::Relaxed);
+                            return (found, overflows);
+                        }
+                        let mut remainder = assignment;
+                        let mut cost = 0;
+                        let mut uses = Some(0);
+                        for op in composed.ops.iter_mut() {
+                            let index = remainder % operations_count;
+                            let operation = operation_dictionary.operation(index);
+                            *op = operation;
+                            cost += config.objective.cost(operation);
+                            uses = uses.and_then(|uses| usage.combine(uses, usage.binary(index)));
+                            remainder /= operations_count;
+                        }
+                        // Assignments using an operation more than allowed are skipped.
+                        let uses = match uses {
+                            Some(uses) => uses,
+                            None => {
+                                progress.explored(per_assignment);
+                                return (found, overflows);
+                            }
+                        };
+                        // Deduplicated alternatives do not need every parenthesisation.
+                        if pruned {
+                            composed.prune(config);
+                        }
+                        let mut indexes: Vec<usize> = vec![0; size];
+                        composed.ints.fill(numbers[0]);
+                        let limit = numbers.len() - 1;
+                        let mut finished = false;
+                        let mut explored = 0;
+                        let mut changed = size - 1;
+                        while !finished {
+                            composed.evaluate(changed, config);
+                            // Numbers used more than allowed, or required numbers left out,
+                            // skip every parenthesisation.
+                            let uses = if sources_limited {
+                                composed
+                                    .ints
+                                    .iter()
+                                    .try_fold(uses, |uses, n| {
+                                        usage.combine(uses, usage.number(*n)?)
+                                    })
+                                    .filter(|uses| usage.satisfies(*uses))
+                            } else {
+                                Some(uses)
+                            };
+                            // Every parenthesisation combines all the numbers.
+                            let numbers_cost: u64 = if numbers_weighted {
+                                composed
+                                    .ints
+                                    .iter()
+                                    .map(|n| config.objective.number_cost(*n))
+                                    .sum()
+                            } else {
+                                0
+                            };
+                            for &(alternative, unary_cost, unary_uses) in &composed.alternatives {
+                                let Some(uses) = uses else {
+                                    break;
+                                };
+                                if composed.redundant[alternative]
+                                    || limited && usage.combine(uses, unary_uses).is_none()
+                                {
+                                    continue;
+                                }
+                                match composed.values[alternative] {
+                                    Ok(v) => {
+                                        let cost = cost + numbers_cost + unary_cost;
+                                        if !targets.is_empty() && !targets.contains(&v)
+                                            || !config.all_sizes
+                                                && dictionary
+                                                    .get(&v)
+                                                    .is_some_and(|s| s.is_better_than(cost, size))
+                                        {
+                                            continue;
+                                        }
+                                        let solution = found
+                                            .entry(v)
+                                            .or_insert_with(|| Solution::empty(size, cost));
+                                        if cost < solution.cost {
+                                            *solution = Solution::empty(size, cost);
+                                        }
+                                        if cost == solution.cost {
+                                            solution.push(
+                                                composed.expression(alternative),
+                                                max_alternatives,
+                                            );
+                                        }
+                                    }
+                                    Err(EvalError::Overflow) => overflows += 1,
+                                    Err(_) => {}
+                                }
+                            }
+                            explored += 1;
+                            if explored == PROGRESS_INTERVAL {
+                                progress.explored(explored * shapes);
+                                explored = 0;
+                                if expired() {
+                                    timed_out.store(true, Ordering::Relaxed);
+                                    break;
+                                }
+                            }
+                            let mut i: usize = 0;
+                            while i < composed.ints.len() && indexes[i] == limit {
+                                indexes[i] = 0;
+                                composed.ints[i] = numbers[0];
+                                i += 1;
+                            }
+                            if i < composed.ints.len() {
+                                indexes[i] += 1;
+                                composed.ints[i] = numbers[indexes[i]];
+                                changed = i;
+                            } else {
+                                finished = true;
+                            }
+                        }
+                        progress.explored(explored * shapes);
+                        let mut discovered = discovered.lock().unwrap();
+                        discovered.extend(found.keys().filter(|v| !dictionary.contains_key(v)));
+                        progress.discovered(dictionary.len() + discovered.len());
+                        (found, overflows)
+                    },
+                )
+                .reduce(
+                    || (HashMap::new(), 0),
+                    |left, right| merge_found(left, right, max_alternatives),
+                );
+            // The assignments of a chunk interrupted by the timeout are not all explored, so
+            // the checkpoint is saved before merging them and resumes at the start of the chunk.
+            if timed_out.load(Ordering::Relaxed) {
+                truncated = Some((size, deadline.interruption()));
+                checkpoint(&state);
+            }
+            let (found, overflows) = merge_found(
+                (std::mem::take(&mut state.found), state.overflows),
+                (found, chunk_overflows),
+                max_alternatives,
+            );
+            state.found = found;
+            state.overflows = overflows;
+            state.next_assignment = end;
+            if truncated.is_some() {
+                break;
+            }
+            // Over the memory budget a single alternative is kept per value, and if that is
+            // not enough the assignments left are skipped.
+            if let Some(max_memory) = config.max_memory {
+                let memory = |state: &Checkpoint| -> usize {
+                    state
+                        .solutions
+                        .values()
+                        .chain(state.found.values())
+                        .map(Solution::memory)
+                        .sum()
+                };
+                if !degraded && memory(&state) > max_memory {
+                    degraded = true;
+                    max_alternatives = Some(1);
+                    for solution in state.solutions.values_mut().chain(state.found.values_mut()) {
+                        solution.degrade();
+                    }
+                }
+                if memory(&state) > max_memory && state.next_assignment < assignments {
+                    truncated = Some((size, Interruption::Memory));
+                    checkpoint(&state);
+                    break;
+                }
+            }
+            if let Some(policy) = &config.checkpoint {
+                if last_checkpoint.elapsed() >= policy.interval {
+                    save(&state, &policy.path, &unsaved);
+                    last_checkpoint = Instant::now();
+                }
+            }
+        }
+        for (v, mut solution) in std::mem::take(&mut state.found) {
+            solution.finish(config);
+            if degraded {
+                solution.degrade();
+            }
+            if v > state.maximum_composed {
+                state.maximum_composed = v;
+            }
+            match state.solutions.entry(v) {
+                Entry::Occupied(mut entry) if config.all_sizes => {
+                    entry.get_mut().add_size(solution)
+                }
+                Entry::Occupied(mut entry) => {
+                    entry.insert(solution);
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(solution);
+                }
+            }
+        }
+        state.size += 1;
+        state.next_assignment = 0;
+        progress.discovered(state.solutions.len());
+        report_solved(
+            config,
+            &state.solutions,
+            Some(size),
+            &mut reported,
+            progress,
+        );
+        progress.size_finished(size);
+        if truncated.is_some() || config.search_complete(&state.solutions, size) {
+            break;
+        }
+    }
+    report_solved(config, &state.solutions, None, &mut reported, progress);
+    if truncated.is_none() {
+        checkpoint(&state);
+    }
+    Solutions::new(
+        state.solutions,
+        state.maximum_composed,
+        state.overflows,
+        state.size - 1,
+        truncated,
+        degraded,
+    )
+    .with_unsaved_checkpoint(unsaved.into_inner())
+}
+
+// The search goes on when a checkpoint cannot be saved, the last failure being told by the
+// solutions.
+fn save(state: &Checkpoint, path: &Path, unsaved: &RefCell<Option<String>>) {
+    if let Err(e) = state.save(path) {
+        unsaved.replace(Some(format!(
+            "unable to save checkpoint to {}: {}",
+            path.display(),
+            e
+        )));
+    }
+}
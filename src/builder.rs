@@ -0,0 +1,274 @@
+use std::{fmt::Display, ops::RangeInclusive, time::Duration};
+
+use crate::{
+    CancellationToken, CheckpointPolicy, Limits, Objective, Operation, OperationDictionary,
+    Semantics, SolverConfig, Strategy,
+};
+
+// Operations combined when none are given, as by the CLI.
+const OPERATIONS: [&str; 4] = ["+", "-", "*", "/"];
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum ConfigError {
+    // Neither `numbers` nor `max_number` was given, or they leave nothing to combine.
+    NoNumbers,
+    NonPositiveNumber(i64),
+    // The symbols given, some of which are not operations.
+    UnknownOperations(Vec<String>),
+    NoMaxSize,
+    Coverage(f64),
+    // More operations, numbers and depth limited or required than the uses can count.
+    TooManyLimits,
+    // A number limited, or required, that is not one of the numbers.
+    LimitedNumber(i64),
+    RequiredNumber(i64),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::NoNumbers => write!(f, "no numbers to combine"),
+            ConfigError::NonPositiveNumber(number) => {
+                write!(f, "numbers must be > 0, was {}", number)
+            }
+            ConfigError::UnknownOperations(symbols) => write!(
+                f,
+                "unrecognised operations found, allowed=[+,-,*,/,^,||,neg] or [add,sub,mul,div,pow,concat,neg], provided={:?}",
+                symbols
+            ),
+            ConfigError::NoMaxSize => write!(f, "max_size must be > 0"),
+            ConfigError::Coverage(coverage) => {
+                write!(f, "coverage must be a fraction in (0, 1], was {}", coverage)
+            }
+            ConfigError::TooManyLimits => write!(
+                f,
+                "at most {} operations, numbers and the depth can be limited or required",
+                Limits::MAX_LIMITED
+            ),
+            ConfigError::LimitedNumber(number) => write!(
+                f,
+                "source_limit limits {}, which is not one of the numbers",
+                number
+            ),
+            ConfigError::RequiredNumber(number) => write!(
+                f,
+                "must_use requires {}, which is not one of the numbers",
+                number
+            ),
+        }
+    }
+}
+
+// Builds a configuration setting only what differs from the defaults, so that the code using
+// it keeps compiling as settings are added. Every setting but the numbers and the maximum size
+// defaults to the CLI's, and `build()` checks them together.
+#[derive(Clone, Debug)]
+pub struct SolverConfigBuilder {
+    operations: Vec<String>,
+    config: SolverConfig,
+}
+
+impl SolverConfig {
+    pub fn builder() -> SolverConfigBuilder {
+        SolverConfigBuilder {
+            operations: OPERATIONS.map(String::from).to_vec(),
+            config: SolverConfig {
+                numbers: vec![],
+                max_size: 0,
+                operations: OperationDictionary::new(&[]).unwrap(),
+                strategy: Strategy::BruteForce,
+                targets: vec![],
+                semantics: Semantics::default(),
+                objective: Objective::default(),
+                limits: Limits::default(),
+                max_alternatives: None,
+                all_sizes: false,
+                cover: None,
+                coverage: None,
+                dedupe: false,
+                count_distinct: false,
+                preference: vec![],
+                prefer_few_sources: false,
+                prefer_shallow: false,
+                timeout: None,
+                cancellation: None,
+                max_memory: None,
+                checkpoint: None,
+            },
+        }
+    }
+}
+
+impl SolverConfigBuilder {
+    // The numbers from 1 to `max_number` included, as the extractors of the game produce.
+    pub fn max_number(mut self, max_number: i64) -> SolverConfigBuilder {
+        self.config.numbers = (1..(max_number + 1)).collect();
+        self
+    }
+
+    pub fn numbers(mut self, numbers: Vec<i64>) -> SolverConfigBuilder {
+        self.config.numbers = numbers;
+        self
+    }
+
+    pub fn max_size(mut self, max_size: usize) -> SolverConfigBuilder {
+        self.config.max_size = max_size;
+        self
+    }
+
+    // The symbols of the operations, e.g. `["+", "*", "neg"]`.
+    pub fn operations<S: AsRef<str>>(mut self, symbols: &[S]) -> SolverConfigBuilder {
+        self.operations = symbols
+            .iter()
+            .map(|symbol| symbol.as_ref().to_string())
+            .collect();
+        self
+    }
+
+    pub fn strategy(mut self, strategy: Strategy) -> SolverConfigBuilder {
+        self.config.strategy = strategy;
+        self
+    }
+
+    pub fn targets(mut self, targets: Vec<i64>) -> SolverConfigBuilder {
+        self.config.targets = targets;
+        self
+    }
+
+    pub fn semantics(mut self, semantics: Semantics) -> SolverConfigBuilder {
+        self.config.semantics = semantics;
+        self
+    }
+
+    pub fn objective(mut self, objective: Objective) -> SolverConfigBuilder {
+        self.config.objective = objective;
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> SolverConfigBuilder {
+        self.config.limits = limits;
+        self
+    }
+
+    pub fn max_alternatives(mut self, max_alternatives: Option<usize>) -> SolverConfigBuilder {
+        self.config.max_alternatives = max_alternatives;
+        self
+    }
+
+    pub fn all_sizes(mut self, all_sizes: bool) -> SolverConfigBuilder {
+        self.config.all_sizes = all_sizes;
+        self
+    }
+
+    pub fn cover(mut self, cover: Option<RangeInclusive<i64>>) -> SolverConfigBuilder {
+        self.config.cover = cover;
+        self
+    }
+
+    pub fn coverage(mut self, coverage: Option<f64>) -> SolverConfigBuilder {
+        self.config.coverage = coverage;
+        self
+    }
+
+    pub fn dedupe(mut self, dedupe: bool) -> SolverConfigBuilder {
+        self.config.dedupe = dedupe;
+        self
+    }
+
+    pub fn count_distinct(mut self, count_distinct: bool) -> SolverConfigBuilder {
+        self.config.count_distinct = count_distinct;
+        self
+    }
+
+    pub fn preference(mut self, preference: Vec<Operation>) -> SolverConfigBuilder {
+        self.config.preference = preference;
+        self
+    }
+
+    pub fn prefer_few_sources(mut self, prefer_few_sources: bool) -> SolverConfigBuilder {
+        self.config.prefer_few_sources = prefer_few_sources;
+        self
+    }
+
+    pub fn prefer_shallow(mut self, prefer_shallow: bool) -> SolverConfigBuilder {
+        self.config.prefer_shallow = prefer_shallow;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Option<Duration>) -> SolverConfigBuilder {
+        self.config.timeout = timeout;
+        self
+    }
+
+    pub fn cancellation(mut self, cancellation: Option<CancellationToken>) -> SolverConfigBuilder {
+        self.config.cancellation = cancellation;
+        self
+    }
+
+    pub fn max_memory(mut self, max_memory: Option<usize>) -> SolverConfigBuilder {
+        self.config.max_memory = max_memory;
+        self
+    }
+
+    pub fn checkpoint(mut self, checkpoint: Option<CheckpointPolicy>) -> SolverConfigBuilder {
+        self.config.checkpoint = checkpoint;
+        self
+    }
+
+    // The configuration, with the numbers sorted and deduplicated.
+    pub fn build(mut self) -> Result<SolverConfig, ConfigError> {
+        let numbers = &mut self.config.numbers;
+        if let Some(number) = numbers.iter().find(|number| **number <= 0) {
+            return Err(ConfigError::NonPositiveNumber(*number));
+        }
+        if numbers.is_empty() {
+            return Err(ConfigError::NoNumbers);
+        }
+        numbers.sort();
+        numbers.dedup();
+        if self.config.max_size == 0 {
+            return Err(ConfigError::NoMaxSize);
+        }
+        self.config.operations = match OperationDictionary::new(&self.operations) {
+            Some(operations) => operations,
+            None => return Err(ConfigError::UnknownOperations(self.operations)),
+        };
+        if let Some(coverage) = self
+            .config
+            .coverage
+            .filter(|coverage| !(*coverage > 0.0 && *coverage <= 1.0))
+        {
+            return Err(ConfigError::Coverage(coverage));
+        }
+        let limits = &self.config.limits;
+        let numbers = &self.config.numbers;
+        if let Some(number) = limits.limited_numbers().find(|n| !numbers.contains(n)) {
+            return Err(ConfigError::LimitedNumber(number));
+        }
+        if let Some(number) = limits.required().iter().find(|n| !numbers.contains(n)) {
+            return Err(ConfigError::RequiredNumber(*number));
+        }
+        // The uses count every limited operation of the dictionary, every number limited or
+        // required and the depth in a field of their own.
+        let operations = &self.config.operations;
+        let limited = operations
+            .operations()
+            .iter()
+            .filter(|operation| limits.max_uses(**operation).is_some())
+            .count()
+            + operations
+                .unary_operations()
+                .iter()
+                .filter(|operation| limits.max_unary_uses(**operation).is_some())
+                .count()
+            + numbers
+                .iter()
+                .filter(|n| limits.max_source_uses(**n).is_some() || limits.required().contains(n))
+                .count()
+            + limits.max_depth().is_some() as usize;
+        if limited > Limits::MAX_LIMITED {
+            return Err(ConfigError::TooManyLimits);
+        }
+        Ok(self.config)
+    }
+}
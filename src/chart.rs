@@ -0,0 +1,213 @@
+use beltmatic_calc::{Solutions, SolverConfig};
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
+use plotters::{coord::Shift, prelude::*};
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::stats::searched;
+
+// The dimensions of the chart, whose three panels are stacked.
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 720;
+const BAR: RGBColor = RGBColor(0x4c, 0x78, 0xa8);
+// The values from 1 to the largest value composed are split into this many ranges.
+const RANGES: i64 = 20;
+
+// The image formats charts are drawn in, told by the extension of the file.
+enum Format {
+    Svg,
+    Png,
+}
+
+fn format(path: &Path) -> Result<Format, String> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) if extension.eq_ignore_ascii_case("svg") => Ok(Format::Svg),
+        Some(extension) if extension.eq_ignore_ascii_case("png") => Ok(Format::Png),
+        _ => Err(format!(
+            "charts are drawn as SVG or PNG, the file must end with .svg or .png, was {}",
+            path.display()
+        )),
+    }
+}
+
+// Checks that the chart can be drawn to the path, before searching.
+pub(crate) fn check(path: &Path) -> Result<(), String> {
+    format(path).map(|_| ())
+}
+
+// A bar of a panel, with its label under the horizontal axis.
+struct Bar {
+    label: String,
+    value: f64,
+}
+
+struct Panel {
+    title: String,
+    unit: &'static str,
+    bars: Vec<Bar>,
+}
+
+// Draws the values solved with each size, the fraction of the values from 1 to the largest
+// value composed solved up to each size, and the mean size of the values of each range of them,
+// the unsolved values counting as one more than the largest size searched, as three panels of
+// an image in the format of the path.
+pub(crate) fn write(
+    out: &mut dyn Write,
+    path: &Path,
+    config: &SolverConfig,
+    solutions: &Solutions,
+) -> io::Result<()> {
+    let panels = panels(config, solutions);
+    let failed = |e: String| io::Error::other(e);
+    match format(path).map_err(failed)? {
+        Format::Svg => {
+            let mut svg = String::new();
+            draw(
+                &SVGBackend::with_string(&mut svg, (WIDTH, HEIGHT)).into_drawing_area(),
+                &panels,
+            )
+            .map_err(failed)?;
+            out.write_all(svg.as_bytes())
+        }
+        Format::Png => {
+            let mut pixels = vec![0; (WIDTH * HEIGHT * 3) as usize];
+            draw(
+                &BitMapBackend::with_buffer(&mut pixels, (WIDTH, HEIGHT)).into_drawing_area(),
+                &panels,
+            )
+            .map_err(failed)?;
+            PngEncoder::new(out)
+                .write_image(&pixels, WIDTH, HEIGHT, ColorType::Rgb8)
+                .map_err(|e| failed(e.to_string()))
+        }
+    }
+}
+
+fn panels(config: &SolverConfig, solutions: &Solutions) -> Vec<Panel> {
+    let largest = solutions.maximum_composed().max(1);
+    let sizes = solutions.size_searched();
+    let step = (largest + RANGES - 1) / RANGES;
+    let mut values = vec![0usize; sizes + 1];
+    let mut covered = vec![0usize; sizes + 1];
+    // The number of values solved in each range, and the sum of their sizes.
+    let mut ranges = vec![(0usize, 0usize); RANGES as usize];
+    for (value, solution) in searched(config, solutions) {
+        values[solution.size.min(sizes)] += 1;
+        if (1..=largest).contains(&value) {
+            covered[solution.size.min(sizes)] += 1;
+            let range = &mut ranges[((value - 1) / step) as usize];
+            range.0 += 1;
+            range.1 += solution.size;
+        }
+    }
+    let by_size = (1..=sizes)
+        .map(|size| Bar {
+            label: size.to_string(),
+            value: values[size] as f64,
+        })
+        .collect();
+    let mut solved = 0;
+    let coverage = (1..=sizes)
+        .map(|size| {
+            solved += covered[size];
+            Bar {
+                label: size.to_string(),
+                value: 100.0 * solved as f64 / largest as f64,
+            }
+        })
+        .collect();
+    let ranges = (0..RANGES)
+        .map(|range| (1 + range * step, ((range + 1) * step).min(largest)))
+        .take_while(|(from, to)| from <= to)
+        .zip(ranges)
+        .map(|((from, to), (solved, total))| {
+            let unsolved = (to - from + 1) as usize - solved;
+            Bar {
+                label: from.to_string(),
+                value: (total + unsolved * (sizes + 1)) as f64 / (to - from + 1) as f64,
+            }
+        })
+        .collect();
+    vec![
+        Panel {
+            title: "Values solved by size".to_string(),
+            unit: "",
+            bars: by_size,
+        },
+        Panel {
+            title: format!("Values of 1..{} solved up to each size", largest),
+            unit: "%",
+            bars: coverage,
+        },
+        Panel {
+            title: format!(
+                "Mean size of the values of each range, unsolved counting as {}",
+                sizes + 1
+            ),
+            unit: "",
+            bars: ranges,
+        },
+    ]
+}
+
+// Draws every panel as a bar chart, one under the other, labelling the bars under the
+// horizontal axis.
+fn draw<DB: DrawingBackend>(root: &DrawingArea<DB, Shift>, panels: &[Panel]) -> Result<(), String>
+where
+    DB::ErrorType: 'static,
+{
+    let failed = |e: DrawingAreaErrorKind<DB::ErrorType>| e.to_string();
+    root.fill(&WHITE).map_err(failed)?;
+    for (area, panel) in root.split_evenly((panels.len(), 1)).iter().zip(panels) {
+        let highest = panel.bars.iter().map(|bar| bar.value).fold(0.0, f64::max);
+        let mut chart = ChartBuilder::on(area)
+            .caption(&panel.title, ("sans-serif", 15))
+            .margin(10)
+            .x_label_area_size(25)
+            .y_label_area_size(50)
+            .build_cartesian_2d(
+                // Segmenting the range gives a segment to its end too.
+                (0..panel.bars.len().max(1) - 1).into_segmented(),
+                0.0..highest.max(1.0) * 1.05,
+            )
+            .map_err(failed)?;
+        let label = |segment: &SegmentValue<usize>| match segment {
+            SegmentValue::CenterOf(i) => panel
+                .bars
+                .get(*i)
+                .map_or(String::new(), |bar| bar.label.clone()),
+            _ => String::new(),
+        };
+        let tick = |value: &f64| format!("{}{}", number(*value), panel.unit);
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .x_labels(panel.bars.len().min(RANGES as usize))
+            .x_label_formatter(&label)
+            .x_label_style(("sans-serif", 10))
+            .y_labels(3)
+            .y_label_formatter(&tick)
+            .draw()
+            .map_err(failed)?;
+        chart
+            .draw_series(
+                Histogram::vertical(&chart)
+                    .style(BAR.filled())
+                    .margin(4)
+                    .data(panel.bars.iter().enumerate().map(|(i, bar)| (i, bar.value))),
+            )
+            .map_err(failed)?;
+    }
+    root.present().map_err(failed)
+}
+
+// Whole numbers are drawn without decimals, the others with one.
+fn number(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value)
+    } else {
+        format!("{:.1}", value)
+    }
+}
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{Operation, Semantics, Solution, SolverConfig, UnaryOperation};
+
+// Where and how often the brute-force search saves its state.
+#[derive(Clone, Debug)]
+pub struct CheckpointPolicy {
+    pub path: PathBuf,
+    pub interval: Duration,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum CheckpointError {
+    UnsupportedStrategy,
+    ConfigMismatch,
+}
+
+impl Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::UnsupportedStrategy => {
+                write!(
+                    f,
+                    "only the brute-force solver can resume from a checkpoint"
+                )
+            }
+            CheckpointError::ConfigMismatch => {
+                write!(
+                    f,
+                    "the checkpoint was saved by a search with different settings"
+                )
+            }
+        }
+    }
+}
+
+// The settings that determine the content of the dictionary. The maximum size is not part of
+// them, so that a completed search can be resumed to extend it to larger sizes.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
+struct Fingerprint {
+    numbers: Vec<i64>,
+    operations: Vec<Operation>,
+    costs: Vec<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    unary_operations: Vec<UnaryOperation>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    unary_costs: Vec<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    number_costs: Vec<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    max_uses: Vec<Option<usize>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    max_unary_uses: Vec<Option<usize>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    max_source_uses: Vec<Option<usize>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    forbidden_adjacent: Vec<(Operation, Operation)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    required: Vec<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_depth: Option<usize>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    chains_only: bool,
+    targets: Vec<i64>,
+    semantics: Semantics,
+    max_alternatives: Option<usize>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    all_sizes: bool,
+    dedupe: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    count_distinct: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    preference: Vec<Operation>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    prefer_few_sources: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    prefer_shallow: bool,
+}
+
+impl Fingerprint {
+    fn new(config: &SolverConfig) -> Fingerprint {
+        let operations = config.operations.operations().to_vec();
+        let unary_operations = config.operations.unary_operations().to_vec();
+        // Limits are left out of the searches without them, which keep their previous keys.
+        let (max_uses, max_unary_uses) = if config.limits.is_limited() {
+            (
+                operations
+                    .iter()
+                    .map(|operation| config.limits.max_uses(*operation))
+                    .collect(),
+                unary_operations
+                    .iter()
+                    .map(|operation| config.limits.max_unary_uses(*operation))
+                    .collect(),
+            )
+        } else {
+            (vec![], vec![])
+        };
+        let max_source_uses: Vec<Option<usize>> = config
+            .numbers
+            .iter()
+            .map(|number| config.limits.max_source_uses(*number))
+            .collect();
+        let max_source_uses = if max_source_uses.iter().any(Option::is_some) {
+            max_source_uses
+        } else {
+            vec![]
+        };
+        let number_costs = if config.objective.has_number_costs() {
+            config
+                .numbers
+                .iter()
+                .map(|number| config.objective.number_cost(*number))
+                .collect()
+        } else {
+            vec![]
+        };
+        Fingerprint {
+            numbers: config.numbers.clone(),
+            costs: operations
+                .iter()
+                .map(|operation| config.objective.cost(*operation))
+                .collect(),
+            operations,
+            unary_costs: unary_operations
+                .iter()
+                .map(|operation| config.objective.unary_cost(*operation))
+                .collect(),
+            number_costs,
+            max_uses,
+            max_unary_uses,
+            max_source_uses,
+            forbidden_adjacent: config.limits.forbidden_adjacent().to_vec(),
+            required: config.limits.required().to_vec(),
+            max_depth: config.limits.max_depth(),
+            chains_only: config.limits.chains_only(),
+            unary_operations,
+            targets: config.targets.clone(),
+            semantics: config.semantics,
+            max_alternatives: config.max_alternatives,
+            all_sizes: config.all_sizes,
+            dedupe: config.dedupe,
+            count_distinct: config.count_distinct,
+            preference: config.preference.clone(),
+            prefer_few_sources: config.prefer_few_sources,
+            prefer_shallow: config.prefer_shallow,
+        }
+    }
+}
+
+// Every size below `size` has been searched completely, and so have the operator assignments
+// of `size` below `next_assignment`, whose results are collected in `found`.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    fingerprint: Fingerprint,
+    pub(crate) size: usize,
+    pub(crate) next_assignment: usize,
+    pub(crate) solutions: HashMap<i64, Solution>,
+    pub(crate) found: HashMap<i64, Solution>,
+    pub(crate) maximum_composed: i64,
+    pub(crate) overflows: u64,
+}
+
+impl Checkpoint {
+    pub(crate) fn new(config: &SolverConfig) -> Checkpoint {
+        Checkpoint {
+            fingerprint: Fingerprint::new(config),
+            size: 1,
+            next_assignment: 0,
+            solutions: HashMap::new(),
+            found: HashMap::new(),
+            maximum_composed: 1,
+            overflows: 0,
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Checkpoint> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    // The checkpoint is written next to `path` and renamed over it, so that an interrupted save
+    // never leaves a truncated checkpoint behind.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&temporary, path)
+    }
+
+    // The size the search will continue from.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // A name for the checkpoints of the searches sharing the settings of `config`, stable across
+    // runs so that checkpoints can be cached on disk: the FNV-1a hash of the settings.
+    pub fn key(config: &SolverConfig) -> String {
+        let settings =
+            serde_json::to_vec(&Fingerprint::new(config)).expect("settings are serializable");
+        let hash = settings.iter().fold(0xcbf29ce484222325, |hash: u64, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        });
+        format!("{:016x}", hash)
+    }
+
+    pub(crate) fn matches(&self, config: &SolverConfig) -> bool {
+        self.fingerprint == Fingerprint::new(config)
+    }
+}
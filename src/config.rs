@@ -0,0 +1,159 @@
+use clap::{Arg, Command, CommandFactory};
+use std::{collections::HashSet, ffi::OsString, fs};
+use toml::{Table, Value};
+
+use crate::{
+    failure::{fail, Failure},
+    Cli,
+};
+
+// Finds the value of `--config <file>` or `--config=<file>`.
+fn config_path(args: &[OsString]) -> Option<String> {
+    let mut args = args.iter().map(|arg| arg.to_string_lossy());
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(|path| path.to_string());
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(path.to_string());
+        }
+    }
+    None
+}
+
+// Finds the position and name of the subcommand, which can follow `--config <file>`.
+fn subcommand(args: &[OsString]) -> Option<(usize, String)> {
+    let mut position = 1;
+    while let Some(arg) = args.get(position).map(|arg| arg.to_string_lossy()) {
+        if arg == "--config" {
+            position += 2;
+        } else if arg.starts_with("--config=") {
+            position += 1;
+        } else {
+            return Some((position, arg.to_string()));
+        }
+    }
+    None
+}
+
+fn long_names(command: &Command) -> HashSet<String> {
+    let mut names: HashSet<String> = command
+        .get_arguments()
+        .filter_map(|arg| arg.get_long().map(|long| long.to_string()))
+        .collect();
+    for subcommand in command.get_subcommands() {
+        names.extend(long_names(subcommand));
+    }
+    names
+}
+
+fn present(args: &[OsString], arg: &Arg) -> bool {
+    arg.get_long().is_some_and(|long| {
+        let flag = format!("--{}", long);
+        args.iter().any(|given| {
+            let given = given.to_string_lossy();
+            given == flag || given.starts_with(&format!("{}=", flag))
+        })
+    })
+}
+
+// Arrays are written as CSV lists, and tables as CSV lists of `<key>:<value>` entries, e.g.
+// `op_cost = { "/" = 3 }` as `/:3`.
+fn flag_value(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Integer(n) => Some(n.to_string()),
+        Value::Float(n) => Some(n.to_string()),
+        Value::Array(values) => values
+            .iter()
+            .map(flag_value)
+            .collect::<Option<Vec<String>>>()
+            .map(|values| values.join(",")),
+        Value::Table(table) => table
+            .iter()
+            .map(|(key, value)| flag_value(value).map(|value| format!("{}:{}", key, value)))
+            .collect::<Option<Vec<String>>>()
+            .map(|entries| entries.join(",")),
+        Value::Boolean(_) | Value::Datetime(_) => None,
+    }
+}
+
+// Inserts the settings of the configuration file given with `--config` before the arguments of
+// the command line, which override them. Settings are named after the long arguments, with
+// either `_` or `-` as separator, and are ignored by the commands not accepting them.
+pub(crate) fn with_config(args: Vec<OsString>) -> Vec<OsString> {
+    let path = match config_path(&args) {
+        Some(path) => path,
+        None => return args,
+    };
+    let table: Table = match fs::read_to_string(&path).map(|content| content.parse()) {
+        Ok(Ok(table)) => table,
+        Ok(Err(e)) => {
+            fail(
+                Failure::Arguments,
+                format!("unable to parse configuration {}: {}", path, e),
+            );
+        }
+        Err(e) => {
+            fail(
+                Failure::Io,
+                format!("unable to read configuration {}: {}", path, e),
+            );
+        }
+    };
+    let root = Cli::command();
+    let known = long_names(&root);
+    let (position, command) = match subcommand(&args).and_then(|(position, name)| {
+        root.find_subcommand(&name)
+            .map(|command| (position, command))
+    }) {
+        Some((position, subcommand)) => (position + 1, subcommand),
+        None => (1, &root),
+    };
+    let given = &args[position..];
+    let mut settings: Vec<OsString> = vec![];
+    for (key, value) in &table {
+        let long = key.replace('_', "-");
+        if !known.contains(&long) {
+            fail(
+                Failure::Arguments,
+                format!("unknown setting {} in configuration {}", key, path),
+            );
+        }
+        let arg = match command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(long.as_str()))
+        {
+            Some(arg) => arg,
+            None => continue,
+        };
+        if present(given, arg)
+            || command
+                .get_arg_conflicts_with(arg)
+                .iter()
+                .any(|conflict| present(given, conflict))
+        {
+            continue;
+        }
+        match value {
+            Value::Boolean(true) => settings.push(format!("--{}", long).into()),
+            Value::Boolean(false) => {}
+            value => match flag_value(value) {
+                Some(value) => {
+                    settings.push(format!("--{}", long).into());
+                    settings.push(value.into());
+                }
+                None => {
+                    fail(
+                        Failure::Arguments,
+                        format!("unsupported value for {} in configuration {}", key, path),
+                    );
+                }
+            },
+        }
+    }
+    let mut expanded = args[..position].to_vec();
+    expanded.extend(settings);
+    expanded.extend_from_slice(given);
+    expanded
+}
@@ -0,0 +1,404 @@
+use rayon::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    mem::size_of,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    limits::{Root, Usage, Uses},
+    progress::report_solved,
+    EvalError, Expression, Interruption, Operation, Progress, Solution, Solutions, SolverConfig,
+    UnaryOperation,
+};
+
+// Values are reached with a given number of uses of the limited operations, and a given depth,
+// so that expressions exceeding the limits are never built from them, and with a given root, so
+// that operations are never fed by those they are forbidden to. Without limits every value is
+// reached with no uses and no root.
+type Key = (i64, Uses, Root);
+
+#[derive(Clone, Copy)]
+enum Derivation {
+    Leaf,
+    Combined {
+        operation: Operation,
+        split: usize,
+        left: Key,
+        right: Key,
+    },
+    Unary {
+        operation: UnaryOperation,
+        operand: Key,
+    },
+}
+
+// Cheapest cost of the expressions with a given size, value and uses, and the derivations that
+// achieve it. Costs are additive, so the subexpressions of a cheapest expression are themselves
+// the cheapest for their size, value and uses.
+#[derive(Default)]
+struct Reachable {
+    cost: u64,
+    count: u64,
+    derivations: Vec<Derivation>,
+}
+
+impl Reachable {
+    // Once `degraded` only the first derivation is kept, while the count includes them all.
+    fn add(&mut self, cost: u64, count: u64, derivation: Derivation, degraded: bool) {
+        if self.derivations.is_empty() || cost < self.cost {
+            self.cost = cost;
+            self.count = 0;
+            self.derivations.clear();
+        }
+        if cost == self.cost {
+            self.count = self.count.saturating_add(count);
+            if !degraded || self.derivations.is_empty() {
+                self.derivations.push(derivation);
+            }
+        }
+    }
+
+    // Keeps the first derivation only, to save memory. Unary derivations are added last, so
+    // the operands of unary operations keep their derivations without them.
+    fn degrade(&mut self) {
+        self.derivations.truncate(1);
+        self.derivations.shrink_to_fit();
+    }
+}
+
+// layers[size] maps every value and uses reachable with exactly `size` leaves to the ways it
+// can be obtained by combining values of two smaller layers.
+type Layer = BTreeMap<Key, Reachable>;
+
+// Approximate number of bytes used by a layer.
+fn memory(layer: &Layer) -> usize {
+    layer
+        .values()
+        .map(|reachable| {
+            size_of::<(Key, Reachable)>()
+                + reachable.derivations.capacity() * size_of::<Derivation>()
+        })
+        .sum()
+}
+
+// Expands at most `limit` of the expressions with the given size, value and uses. The operands
+// of unary operations are only expanded through their other derivations, as applying two unary
+// operations in a row is never cheaper than applying none.
+fn expand(layers: &[Layer], size: usize, key: Key, limit: usize, unary: bool) -> Vec<Expression> {
+    let mut result = Vec::new();
+    for derivation in layers[size][&key].derivations.iter() {
+        if result.len() == limit {
+            break;
+        }
+        match *derivation {
+            Derivation::Leaf => result.push(Expression::Number(key.0)),
+            Derivation::Combined {
+                operation,
+                split,
+                left,
+                right,
+            } => {
+                let remaining = limit - result.len();
+                let left_expansions = expand(layers, split, left, remaining, true);
+                let right_expansions = expand(layers, size - split, right, remaining, true);
+                for l in left_expansions.iter() {
+                    for r in right_expansions.iter() {
+                        if result.len() == limit {
+                            break;
+                        }
+                        result.push(Expression::binary(operation, l.clone(), r.clone()));
+                    }
+                }
+            }
+            Derivation::Unary { operation, operand } if unary => {
+                let remaining = limit - result.len();
+                for expansion in expand(layers, size, operand, remaining, false) {
+                    result.push(Expression::unary(operation, expansion));
+                }
+            }
+            Derivation::Unary { .. } => {}
+        }
+    }
+    result
+}
+
+type Candidate = (Result<i64, EvalError>, Uses, Root, u64, u64, Derivation);
+
+// Searches the positive values, and the targets whatever their sign. Brute force also keeps the
+// other values it composes, zero and the negative ones, which only it can report.
+pub(crate) fn search(config: &SolverConfig, progress: &dyn Progress) -> Solutions {
+    let targets = config.target_set();
+    search_values(config, progress, &|v| v > 0 || targets.contains(&v))
+}
+
+// Searches the values accepted by `keep` rather than the positive ones only, which the other
+// strategies use to build tables of operands.
+pub(crate) fn search_values(
+    config: &SolverConfig,
+    progress: &dyn Progress,
+    keep: &(dyn Fn(i64) -> bool + Sync),
+) -> Solutions {
+    let operation_dictionary = &config.operations;
+    let maximum_size = config.max_size;
+    let targets = config.target_set();
+    let usage = Usage::new(config);
+    let mut dictionary: HashMap<i64, Solution> = HashMap::new();
+    let mut maximum_composed = 1;
+    let mut overflows = 0;
+    let mut size_searched = 0;
+    let mut truncated = None;
+    let deadline = config.deadline();
+    let expired = || deadline.expired();
+    let timed_out = AtomicBool::new(false);
+    let mut degraded = false;
+    let mut reported = HashSet::new();
+    let mut layers: Vec<Layer> = vec![Layer::new()];
+    let operations_count = operation_dictionary.operations().len() as u64;
+    for size in 1..(maximum_size + 1) {
+        if expired() {
+            truncated = Some((size, deadline.interruption()));
+            break;
+        }
+        let leaves = if size == 1 { config.numbers.len() } else { 0 };
+        let combinations: u64 = config
+            .limits
+            .splits(size)
+            .map(|split| {
+                layers[split].len() as u64 * layers[size - split].len() as u64 * operations_count
+            })
+            .sum();
+        progress.size_started(size, leaves as u64 + combinations);
+        let mut layer = Layer::new();
+        if size == 1 {
+            for &n in config.numbers.iter() {
+                if let Some(uses) = usage.number(n) {
+                    layer.entry((n, uses, 0)).or_default().add(
+                        config.objective.number_cost(n),
+                        1,
+                        Derivation::Leaf,
+                        degraded,
+                    );
+                }
+            }
+            progress.explored(leaves as u64);
+        }
+        let usage = &usage;
+        // Once the timeout expires the operands left are skipped, and the values of the layer
+        // are the cheapest found with the operands explored.
+        for split in config.limits.splits(size) {
+            if timed_out.load(Ordering::Relaxed) {
+                truncated = Some((size, deadline.interruption()));
+                break;
+            }
+            let lefts: Vec<(Key, &Reachable)> = layers[split]
+                .iter()
+                .map(|(key, reachable)| (*key, reachable))
+                .collect();
+            let rights = &layers[size - split];
+            let combined: Vec<Candidate> = lefts
+                .par_iter()
+                .filter(|_| {
+                    if expired() {
+                        timed_out.store(true, Ordering::Relaxed);
+                    }
+                    !timed_out.load(Ordering::Relaxed)
+                })
+                .flat_map_iter(|&(left, left_reachable)| {
+                    progress.explored(rights.len() as u64 * operations_count);
+                    rights.iter().flat_map(move |(&right, right_reachable)| {
+                        operation_dictionary
+                            .operations()
+                            .iter()
+                            .enumerate()
+                            .filter_map(move |(index, &operation)| {
+                                if !usage.feeds(left.2, index) || !usage.feeds(right.2, index) {
+                                    return None;
+                                }
+                                if config.is_wasteful(
+                                    operation,
+                                    left.0,
+                                    right.0,
+                                    split > 1 || size - split > 1,
+                                ) {
+                                    return None;
+                                }
+                                let uses = usage
+                                    .combine(left.1, right.1)
+                                    .and_then(|uses| usage.combine(uses, usage.binary(index)))
+                                    .and_then(|uses| usage.deepen(uses))?;
+                                Some((
+                                    operation.apply(left.0, right.0, config.semantics),
+                                    uses,
+                                    usage.root(index),
+                                    left_reachable.cost
+                                        + right_reachable.cost
+                                        + config.objective.cost(operation),
+                                    left_reachable.count.saturating_mul(right_reachable.count),
+                                    Derivation::Combined {
+                                        operation,
+                                        split,
+                                        left,
+                                        right,
+                                    },
+                                ))
+                            })
+                    })
+                })
+                .collect();
+            for (v, uses, root, cost, count, derivation) in combined {
+                match v {
+                    Ok(v) => layer
+                        .entry((v, uses, root))
+                        .or_default()
+                        .add(cost, count, derivation, degraded),
+                    Err(EvalError::Overflow) => overflows += 1,
+                    Err(_) => {}
+                }
+            }
+            // Over the memory budget a single derivation and alternative are kept per value,
+            // and if that is not enough the splits left are skipped.
+            if let Some(max_memory) = config.max_memory {
+                let used =
+                    |layers: &[Layer], layer: &Layer, dictionary: &HashMap<i64, Solution>| {
+                        layers.iter().map(memory).sum::<usize>()
+                            + memory(layer)
+                            + dictionary.values().map(Solution::memory).sum::<usize>()
+                    };
+                if !degraded && used(&layers, &layer, &dictionary) > max_memory {
+                    degraded = true;
+                    for reachable in layers
+                        .iter_mut()
+                        .chain([&mut layer])
+                        .flat_map(|layer| layer.values_mut())
+                    {
+                        reachable.degrade();
+                    }
+                    for solution in dictionary.values_mut() {
+                        solution.degrade();
+                    }
+                }
+                if split < size - 1 && used(&layers, &layer, &dictionary) > max_memory {
+                    truncated = Some((size, Interruption::Memory));
+                    break;
+                }
+            }
+        }
+        if truncated.is_none() && timed_out.load(Ordering::Relaxed) {
+            truncated = Some((size, deadline.interruption()));
+        }
+        // Unary operations are applied to the values reached without them, and the resulting
+        // derivations only count those reaching their operand without them either.
+        let unary: Vec<Candidate> = layer
+            .iter()
+            .flat_map(|(&operand, reachable)| {
+                operation_dictionary
+                    .unary_operations()
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(index, &operation)| {
+                        let uses = usage.deepen(usage.combine(operand.1, usage.unary(index))?)?;
+                        Some((
+                            operation.apply(operand.0, config.semantics),
+                            uses,
+                            0,
+                            reachable.cost + config.objective.unary_cost(operation),
+                            reachable.count,
+                            Derivation::Unary { operation, operand },
+                        ))
+                    })
+            })
+            .collect();
+        for (v, uses, root, cost, count, derivation) in unary {
+            match v {
+                Ok(v) => layer
+                    .entry((v, uses, root))
+                    .or_default()
+                    .add(cost, count, derivation, degraded),
+                Err(EvalError::Overflow) => overflows += 1,
+                Err(_) => {}
+            }
+        }
+        layers.push(layer);
+        // The cheapest uses and roots of every value using the required numbers, in increasing
+        // order of value.
+        let mut cheapest: Vec<(i64, u64, Vec<Key>)> = vec![];
+        for (&(v, uses, root), reachable) in layers[size].iter() {
+            if !usage.satisfies(uses) {
+                continue;
+            }
+            match cheapest.last_mut() {
+                Some((last, cost, keys)) if *last == v => {
+                    if reachable.cost < *cost {
+                        *cost = reachable.cost;
+                        keys.clear();
+                    }
+                    if reachable.cost == *cost {
+                        keys.push((v, uses, root));
+                    }
+                }
+                _ => cheapest.push((v, reachable.cost, vec![(v, uses, root)])),
+            }
+        }
+        let discovered: Vec<(i64, u64, Vec<Key>)> = cheapest
+            .into_iter()
+            .filter(|(v, cost, _)| {
+                (targets.is_empty() || targets.contains(v))
+                    && !dictionary
+                        .get(v)
+                        .is_some_and(|s| s.is_better_than(*cost, size))
+            })
+            .collect();
+        let limit = if degraded {
+            1
+        } else if config.keeps_all_alternatives() {
+            usize::MAX
+        } else {
+            config.max_alternatives.unwrap_or(usize::MAX)
+        };
+        // The values left to expand when the timeout expires are dropped.
+        let kept = discovered.iter().filter(|(v, _, _)| keep(*v)).count();
+        let expanded: Vec<(i64, Solution)> = discovered
+            .par_iter()
+            .filter(|(v, _, _)| keep(*v) && !expired())
+            .map(|(v, cost, keys)| {
+                let mut solution = Solution::empty(size, *cost);
+                for key in keys {
+                    let remaining = limit - solution.alternatives.len();
+                    solution.count = solution.count.saturating_add(layers[size][key].count);
+                    solution
+                        .alternatives
+                        .extend(expand(&layers, size, *key, remaining, true));
+                }
+                solution.finish(config);
+                if degraded {
+                    solution.degrade();
+                }
+                (*v, solution)
+            })
+            .collect();
+        if truncated.is_none() && expanded.len() < kept {
+            truncated = Some((size, deadline.interruption()));
+        }
+        for (v, solution) in expanded {
+            maximum_composed = maximum_composed.max(v);
+            dictionary.insert(v, solution);
+        }
+        progress.discovered(dictionary.len());
+        report_solved(config, &dictionary, Some(size), &mut reported, progress);
+        progress.size_finished(size);
+        size_searched = size;
+        if truncated.is_some() || config.search_complete(&dictionary, size) {
+            break;
+        }
+    }
+    report_solved(config, &dictionary, None, &mut reported, progress);
+    Solutions::new(
+        dictionary,
+        maximum_composed,
+        overflows,
+        size_searched,
+        truncated,
+        degraded,
+    )
+}
@@ -0,0 +1,75 @@
+use serde::Serialize;
+
+use crate::{Solver, SolverConfig, Strategy};
+
+// Largest size searched for a target. The search stops at the first size producing it, so
+// only unreachable targets explore all of them.
+const MAX_SIZE: usize = 6;
+// Largest number the callers can combine up to: the leaves are allocated upfront, and an
+// allocation failing aborts across the FFI boundary instead of returning an error.
+const MAX_NUMBER: u32 = 1000;
+
+#[derive(Serialize)]
+struct EmbeddedSolution {
+    value: i64,
+    size: Option<usize>,
+    cost: Option<u64>,
+    count: u64,
+    alternatives: Vec<String>,
+}
+
+// Solves `target` combining the numbers in `1..max_number`, at most `MAX_NUMBER`, with the CSV
+// list of operations `ops`, returning the solution as a JSON document with the same fields as
+// the CLI output.
+pub(crate) fn solve(target: i64, max_number: u32, ops: &str) -> Result<String, String> {
+    if max_number == 0 {
+        return Err("max_number must be > 0".to_string());
+    }
+    if max_number > MAX_NUMBER {
+        return Err(format!(
+            "max_number must be at most {}, was {}",
+            MAX_NUMBER, max_number
+        ));
+    }
+    let operations: Vec<&str> = ops.split(",").map(str::trim).collect();
+    let config = SolverConfig::builder()
+        .max_number(max_number as i64)
+        .max_size(MAX_SIZE)
+        .operations(&operations)
+        .strategy(Strategy::Dynamic)
+        .targets(vec![target])
+        .build()
+        .map_err(|e| e.to_string())?;
+    let solutions = Solver::new(config).solve();
+    let solution = solutions.get(target);
+    let solution = EmbeddedSolution {
+        value: target,
+        size: solution.map(|solution| solution.size),
+        cost: solution.map(|solution| solution.cost),
+        count: solution.map_or(0, |solution| solution.count),
+        alternatives: solution
+            .map(|solution| {
+                solution
+                    .alternatives
+                    .iter()
+                    .map(|alternative| alternative.to_string())
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+    serde_json::to_string(&solution).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_number_is_bounded() {
+        assert_eq!(
+            solve(97, 4_000_000_000, "+,*"),
+            Err("max_number must be at most 1000, was 4000000000".to_string())
+        );
+        assert!(solve(97, MAX_NUMBER, "+,*").is_ok());
+    }
+}
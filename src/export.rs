@@ -0,0 +1,115 @@
+use beltmatic_calc::{Solutions, SolverConfig};
+use rusqlite::{params, Connection};
+use std::{fs, path::PathBuf};
+
+use crate::{difficulty, minimal, Annotations, JsonMetadata, Syntax};
+
+// The databases results can be exported to, given as `<kind>:<path>`.
+pub(crate) enum Export {
+    Sqlite(PathBuf),
+}
+
+impl Export {
+    pub(crate) fn parse(spec: &str) -> Result<Export, String> {
+        match spec.split_once(':') {
+            Some(("sqlite", path)) if !path.is_empty() => Ok(Export::Sqlite(PathBuf::from(path))),
+            _ => Err(format!("export must be sqlite:<path>, was {}", spec)),
+        }
+    }
+}
+
+const SCHEMA: &str = "
+CREATE TABLE metadata (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+CREATE TABLE solutions (
+    value INTEGER PRIMARY KEY,
+    size INTEGER,
+    cost INTEGER,
+    count INTEGER NOT NULL,
+    difficulty INTEGER,
+    minimal INTEGER
+);
+CREATE TABLE alternatives (
+    value INTEGER NOT NULL REFERENCES solutions (value),
+    position INTEGER NOT NULL,
+    expression TEXT NOT NULL,
+    PRIMARY KEY (value, position)
+);
+CREATE INDEX solutions_size ON solutions (size);
+CREATE INDEX alternatives_expression ON alternatives (expression);
+";
+
+// Writes the printed values into a new database: the metadata of the search as JSON values by
+// key, whether it weighs the operations, a row per value with its size, cost, count of
+// alternatives and annotations, NULL when unsolved or not requested, and a row per alternative
+// kept, in the order of the solution.
+// The database is built next to the path and renamed over it once complete.
+pub(crate) fn export(
+    export: &Export,
+    solutions: &Solutions,
+    printed: impl Iterator<Item = i64>,
+    metadata: &JsonMetadata,
+    syntax: Syntax,
+    config: &SolverConfig,
+    annotations: &Annotations,
+) -> Result<(), String> {
+    let Export::Sqlite(path) = export;
+    let semantics = config.semantics();
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    let _ = fs::remove_file(&temporary);
+    let metadata = serde_json::to_value(metadata).map_err(|e| e.to_string())?;
+    let result = (|| {
+        let mut database = Connection::open(&temporary)?;
+        database.execute_batch(SCHEMA)?;
+        let transaction = database.transaction()?;
+        {
+            let mut insert = transaction.prepare("INSERT INTO metadata VALUES (?, ?)")?;
+            for (key, value) in metadata.as_object().into_iter().flatten() {
+                insert.execute(params![key, value.to_string()])?;
+            }
+            // Costs are only printed back when they are not the number of operations.
+            insert.execute(params![
+                "weighted",
+                config.objective().is_weighted().to_string()
+            ])?;
+            let mut insert_solution =
+                transaction.prepare("INSERT OR IGNORE INTO solutions VALUES (?, ?, ?, ?, ?, ?)")?;
+            let mut insert_alternative =
+                transaction.prepare("INSERT OR IGNORE INTO alternatives VALUES (?, ?, ?)")?;
+            for v in printed {
+                let solution = solutions.get(v);
+                insert_solution.execute(params![
+                    v,
+                    solution.map(|solution| solution.size as i64),
+                    solution.map(|solution| solution.cost as i64),
+                    solution.map_or(0, |solution| solution.count) as i64,
+                    difficulty(annotations, v, solution).map(|size| size as i64),
+                    solution.and_then(|solution| {
+                        minimal(annotations.proof.as_ref(), v, solution, semantics)
+                    }),
+                ])?;
+                for (position, alternative) in solution
+                    .into_iter()
+                    .flat_map(|solution| solution.alternatives.iter())
+                    .enumerate()
+                {
+                    insert_alternative.execute(params![
+                        v,
+                        position as i64,
+                        syntax.render(alternative, semantics)
+                    ])?;
+                }
+            }
+        }
+        transaction.commit()?;
+        // Closing reports the failure to flush the database that dropping it would ignore.
+        database.close().map_err(|(_, e)| e)
+    })()
+    .map_err(|e: rusqlite::Error| e.to_string())
+    .and_then(|_| fs::rename(&temporary, path).map_err(|e| e.to_string()));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result.map_err(|e| format!("{}: {}", path.display(), e))
+}
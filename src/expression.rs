@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+};
+
+use crate::{EvalError, Operation, Semantics, UnaryOperation};
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Debug, Serialize, Deserialize)]
+pub enum Expression {
+    Number(i64),
+    Binary(Operation, Box<Expression>, Box<Expression>),
+    Unary(UnaryOperation, Box<Expression>),
+}
+
+impl Expression {
+    pub fn binary(operation: Operation, left: Expression, right: Expression) -> Expression {
+        Expression::Binary(operation, Box::new(left), Box::new(right))
+    }
+
+    pub fn unary(operation: UnaryOperation, operand: Expression) -> Expression {
+        Expression::Unary(operation, Box::new(operand))
+    }
+
+    pub fn eval(&self, semantics: Semantics) -> Result<i64, EvalError> {
+        match self {
+            Expression::Number(value) => Ok(*value),
+            Expression::Binary(operation, left, right) => {
+                operation.apply(left.eval(semantics)?, right.eval(semantics)?, semantics)
+            }
+            Expression::Unary(operation, operand) => {
+                operation.apply(operand.eval(semantics)?, semantics)
+            }
+        }
+    }
+
+    // Rewrites every chain of the same commutative operation as a left-leaning chain of sorted
+    // operands, so that expressions equivalent up to commutativity and associativity are equal.
+    pub fn canonical(&self) -> Expression {
+        match self {
+            Expression::Number(_) => self.clone(),
+            Expression::Binary(operation, _, _) if operation.is_commutative() => {
+                let mut chain = vec![];
+                self.collect_chain(*operation, &mut chain);
+                let mut operands: Vec<Expression> = chain
+                    .into_iter()
+                    .map(|operand| operand.canonical())
+                    .collect();
+                operands.sort();
+                let mut operands = operands.into_iter();
+                let first = operands.next().unwrap();
+                operands.fold(first, |left, right| {
+                    Expression::binary(*operation, left, right)
+                })
+            }
+            Expression::Binary(operation, left, right) => {
+                Expression::binary(*operation, left.canonical(), right.canonical())
+            }
+            Expression::Unary(operation, operand) => {
+                Expression::unary(*operation, operand.canonical())
+            }
+        }
+    }
+
+    // Swaps the operands of every commutative operation so that the smaller value comes first,
+    // keeping the parenthesisation. Operands of equal values are ordered as expressions, and those
+    // failing to evaluate are left in place.
+    pub fn order_operands(&self, semantics: Semantics) -> Expression {
+        self.ordered(semantics).0
+    }
+
+    fn ordered(&self, semantics: Semantics) -> (Expression, Result<i64, EvalError>) {
+        match self {
+            Expression::Number(value) => (self.clone(), Ok(*value)),
+            Expression::Binary(operation, left, right) => {
+                let (left, l) = left.ordered(semantics);
+                let (right, r) = right.ordered(semantics);
+                let value = l.and_then(|l| r.and_then(|r| operation.apply(l, r, semantics)));
+                let swap = operation.is_commutative()
+                    && matches!((l, r), (Ok(l), Ok(r)) if (r, &right) < (l, &left));
+                if swap {
+                    (Expression::binary(*operation, right, left), value)
+                } else {
+                    (Expression::binary(*operation, left, right), value)
+                }
+            }
+            Expression::Unary(operation, operand) => {
+                let (operand, value) = operand.ordered(semantics);
+                (
+                    Expression::unary(*operation, operand),
+                    value.and_then(|value| operation.apply(value, semantics)),
+                )
+            }
+        }
+    }
+
+    fn collect_chain<'a>(&'a self, operation: Operation, chain: &mut Vec<&'a Expression>) {
+        match self {
+            Expression::Binary(op, left, right) if *op == operation => {
+                left.collect_chain(operation, chain);
+                right.collect_chain(operation, chain);
+            }
+            _ => chain.push(self),
+        }
+    }
+
+    // The number of machines needed to build the expression.
+    pub fn operations(&self) -> usize {
+        match self {
+            Expression::Number(_) => 0,
+            Expression::Binary(_, left, right) => 1 + left.operations() + right.operations(),
+            Expression::Unary(_, operand) => 1 + operand.operations(),
+        }
+    }
+
+    // The number of machines of the given operation needed to build the expression.
+    pub fn uses(&self, operation: Operation) -> usize {
+        match self {
+            Expression::Number(_) => 0,
+            Expression::Binary(op, left, right) => {
+                usize::from(*op == operation) + left.uses(operation) + right.uses(operation)
+            }
+            Expression::Unary(_, operand) => operand.uses(operation),
+        }
+    }
+
+    // The number of machines of the given unary operation needed to build the expression.
+    pub fn unary_uses(&self, operation: UnaryOperation) -> usize {
+        match self {
+            Expression::Number(_) => 0,
+            Expression::Binary(_, left, right) => {
+                left.unary_uses(operation) + right.unary_uses(operation)
+            }
+            Expression::Unary(op, operand) => {
+                usize::from(*op == operation) + operand.unary_uses(operation)
+            }
+        }
+    }
+
+    // The number of leaves of the given value, each needing an extractor of it.
+    pub fn number_uses(&self, number: i64) -> usize {
+        match self {
+            Expression::Number(value) => usize::from(*value == number),
+            Expression::Binary(_, left, right) => {
+                left.number_uses(number) + right.number_uses(number)
+            }
+            Expression::Unary(_, operand) => operand.number_uses(number),
+        }
+    }
+
+    // The machines of each operation needed to build the expression, by symbol, in the
+    // canonical order of the operations.
+    pub fn machines(&self) -> Vec<(String, usize)> {
+        let mut binary = BTreeMap::new();
+        let mut unary = BTreeMap::new();
+        self.collect_machines(&mut binary, &mut unary);
+        binary
+            .into_iter()
+            .map(|(operation, count)| (operation.to_string(), count))
+            .chain(
+                unary
+                    .into_iter()
+                    .map(|(operation, count)| (operation.to_string(), count)),
+            )
+            .collect()
+    }
+
+    fn collect_machines(
+        &self,
+        binary: &mut BTreeMap<Operation, usize>,
+        unary: &mut BTreeMap<UnaryOperation, usize>,
+    ) {
+        match self {
+            Expression::Number(_) => {}
+            Expression::Binary(operation, left, right) => {
+                *binary.entry(*operation).or_default() += 1;
+                left.collect_machines(binary, unary);
+                right.collect_machines(binary, unary);
+            }
+            Expression::Unary(operation, operand) => {
+                *unary.entry(*operation).or_default() += 1;
+                operand.collect_machines(binary, unary);
+            }
+        }
+    }
+
+    // The extractors of each number needed to build the expression, one per leaf, in increasing
+    // order of number.
+    pub fn extractors(&self) -> Vec<(i64, usize)> {
+        let mut extractors = BTreeMap::new();
+        self.collect_extractors(&mut extractors);
+        extractors.into_iter().collect()
+    }
+
+    fn collect_extractors(&self, extractors: &mut BTreeMap<i64, usize>) {
+        match self {
+            Expression::Number(value) => *extractors.entry(*value).or_default() += 1,
+            Expression::Binary(_, left, right) => {
+                left.collect_extractors(extractors);
+                right.collect_extractors(extractors);
+            }
+            Expression::Unary(_, operand) => operand.collect_extractors(extractors),
+        }
+    }
+
+    // The number of distinct values combined, each needing its own extractor.
+    pub fn sources(&self) -> usize {
+        let mut values = HashSet::new();
+        self.collect_sources(&mut values);
+        values.len()
+    }
+
+    fn collect_sources(&self, values: &mut HashSet<i64>) {
+        match self {
+            Expression::Number(value) => {
+                values.insert(*value);
+            }
+            Expression::Binary(_, left, right) => {
+                left.collect_sources(values);
+                right.collect_sources(values);
+            }
+            Expression::Unary(_, operand) => operand.collect_sources(values),
+        }
+    }
+
+    // The number of values combined, which unary operations do not change.
+    pub fn size(&self) -> usize {
+        match self {
+            Expression::Number(_) => 1,
+            Expression::Binary(_, left, right) => left.size() + right.size(),
+            Expression::Unary(_, operand) => operand.size(),
+        }
+    }
+
+    // The number of operations on the longest way from a leaf to the root.
+    pub fn depth(&self) -> usize {
+        match self {
+            Expression::Number(_) => 0,
+            Expression::Binary(_, left, right) => 1 + left.depth().max(right.depth()),
+            Expression::Unary(_, operand) => 1 + operand.depth(),
+        }
+    }
+
+    // Whether every operation combines the result of the previous one with a single number, on
+    // the right.
+    pub fn is_chain(&self) -> bool {
+        match self {
+            Expression::Number(_) => true,
+            Expression::Binary(_, left, right) => left.is_chain() && right.size() == 1,
+            Expression::Unary(_, operand) => operand.is_chain(),
+        }
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expression::Number(value) => write!(f, "{}", value),
+            Expression::Binary(operation, left, right) => {
+                write!(f, "({}{}{})", left, operation, right)
+            }
+            Expression::Unary(operation, operand) => {
+                write!(f, "({}{})", operation.prefix(), operand)
+            }
+        }
+    }
+}
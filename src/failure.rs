@@ -0,0 +1,102 @@
+use clap::ValueEnum;
+use serde_json::json;
+use std::{
+    ffi::OsString,
+    fmt::Display,
+    process::exit,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub(crate) enum ErrorFormat {
+    Text,
+    Json,
+}
+
+// The ways a command can fail, distinguished by its exit status. Usage errors reported by clap
+// also exit with the status of invalid arguments.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Failure {
+    // The expression verified fails to evaluate or does not match its target.
+    Check,
+    Arguments,
+    // Some targets have no solution within the sizes searched.
+    Unsolved,
+    // The search stopped at the timeout before exploring every size.
+    Timeout,
+    Io,
+    // The search stopped over the memory budget before exploring every size.
+    Memory,
+}
+
+impl Failure {
+    pub(crate) fn code(&self) -> i32 {
+        match self {
+            Failure::Check => 1,
+            Failure::Arguments => 2,
+            Failure::Unsolved => 3,
+            Failure::Timeout => 4,
+            Failure::Io => 5,
+            Failure::Memory => 6,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Failure::Check => "check",
+            Failure::Arguments => "arguments",
+            Failure::Unsolved => "unsolved",
+            Failure::Timeout => "timeout",
+            Failure::Io => "io",
+            Failure::Memory => "memory",
+        }
+    }
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_format(format: ErrorFormat) {
+    JSON.store(matches!(format, ErrorFormat::Json), Ordering::Relaxed);
+}
+
+// Errors can happen before the arguments are parsed, e.g. in the configuration file, so the
+// format is first looked up in the raw arguments.
+pub(crate) fn set_format_from(args: &[OsString]) {
+    let json = args
+        .windows(2)
+        .any(|pair| pair[0] == "--error-format" && pair[1] == "json")
+        || args.iter().any(|arg| arg == "--error-format=json");
+    if json {
+        set_format(ErrorFormat::Json);
+    }
+}
+
+// Usage errors are rendered by clap, or reduced to their first line with `--error-format json`.
+// The help and the version are printed as usual.
+pub(crate) fn usage(e: clap::Error) -> ! {
+    if !JSON.load(Ordering::Relaxed) || !e.use_stderr() {
+        e.exit();
+    }
+    let rendered = e.to_string();
+    let message = rendered.lines().next().unwrap_or_default();
+    fail(Failure::Arguments, message.trim_start_matches("error: "))
+}
+
+// Prints the message on standard error, apart from the output piped to other tools, as a JSON
+// object with the name and status of the failure with `--error-format json`, and exits with the
+// status of the failure.
+pub(crate) fn fail(failure: Failure, message: impl Display) -> ! {
+    if JSON.load(Ordering::Relaxed) {
+        eprintln!(
+            "{}",
+            json!({
+                "error": failure.name(),
+                "code": failure.code(),
+                "message": message.to_string(),
+            })
+        );
+    } else {
+        eprintln!("{}", message);
+    }
+    exit(failure.code());
+}
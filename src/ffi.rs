@@ -0,0 +1,44 @@
+use std::{
+    ffi::{c_char, CStr, CString},
+    ptr,
+};
+
+use crate::embedded;
+
+/// Solves `target` combining the numbers in `1..max_number` with the CSV list of operations
+/// `ops`, a NUL-terminated string, returning the solution as a JSON document with the same
+/// fields as the CLI output, or `{"error": ...}` when the arguments are invalid. The document
+/// is owned by the caller, who releases it with `beltmatic_free`.
+///
+/// # Safety
+///
+/// `ops` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn beltmatic_solve(
+    target: i64,
+    max_number: u32,
+    ops: *const c_char,
+) -> *mut c_char {
+    let result = if ops.is_null() {
+        Err("ops must not be NULL".to_string())
+    } else {
+        match unsafe { CStr::from_ptr(ops) }.to_str() {
+            Ok(ops) => embedded::solve(target, max_number, ops),
+            Err(e) => Err(format!("ops must be UTF-8: {}", e)),
+        }
+    };
+    let document = result.unwrap_or_else(|e| serde_json::json!({ "error": e }).to_string());
+    CString::new(document).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Releases a document returned by `beltmatic_solve`. NULL is ignored.
+///
+/// # Safety
+///
+/// `document` must have been returned by `beltmatic_solve` and not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn beltmatic_free(document: *mut c_char) {
+    if !document.is_null() {
+        drop(unsafe { CString::from_raw(document) });
+    }
+}
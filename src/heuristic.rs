@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+
+use crate::{
+    dynamic, progress::Unreported, Expression, Operation, Progress, Solution, Solutions,
+    SolverConfig,
+};
+
+// Composes an expression for targets far beyond the sizes that can be searched exhaustively.
+// The minimal expressions of the values reachable within `max_size` are collected first with
+// the dynamic search; a larger target is then split into one of these values times a cofactor,
+// into a multiple of one of the numbers plus or minus a remainder, into a power or into its
+// digits, and the parts are composed recursively, keeping the cheapest split. The expressions
+// found are good but not necessarily minimal. The parts are composed without the required
+// numbers, which only the dynamic search can enforce.
+pub(crate) fn search(config: &SolverConfig, progress: &dyn Progress) -> Solutions {
+    if config.targets.is_empty() || !config.limits.required().is_empty() {
+        return dynamic::search(config, progress);
+    }
+    let mut table_config = config.clone();
+    table_config.targets = vec![];
+    table_config.cover = None;
+    table_config.max_alternatives = Some(1);
+    table_config.dedupe = false;
+    table_config.count_distinct = false;
+    table_config.preference = vec![];
+    table_config.prefer_few_sources = false;
+    table_config.prefer_shallow = false;
+    // The table has no targets, so that it is searched up to `max_size`, but keeps the
+    // negative ones that the numbers compose.
+    let targets = config.target_set();
+    let table = dynamic::search_values(&table_config, &Unreported(progress), &|v| {
+        v > 0 || targets.contains(&v)
+    });
+    let mut composer = Composer::new(config, &table);
+    let mut dictionary = HashMap::new();
+    for &target in config.targets.iter() {
+        let solution = match table.get(target) {
+            Some(solution) => Some(solution.clone()),
+            None => composer.compose(target).map(|expression| Solution {
+                size: expression.size(),
+                cost: config.objective.expression_cost(&expression),
+                count: 1,
+                alternatives: vec![expression],
+                other_sizes: vec![],
+            }),
+        };
+        if let Some(solution) = solution {
+            dictionary.insert(target, solution);
+        }
+    }
+    let mut solved: Vec<(&i64, &Solution)> = dictionary.iter().collect();
+    solved.sort_by_key(|(value, _)| **value);
+    for (value, solution) in solved {
+        progress.solved(*value, solution);
+    }
+    let maximum_composed = dictionary.keys().copied().max().unwrap_or(1);
+    Solutions::new(
+        dictionary,
+        maximum_composed,
+        table.overflows(),
+        table.size_searched(),
+        table.truncated().zip(table.interruption()),
+        table.degraded(),
+    )
+}
+
+struct Composer<'a> {
+    config: &'a SolverConfig,
+    // The first minimal alternative of every value of the table, in increasing order of value.
+    table: Vec<(i64, Expression)>,
+    lookup: HashMap<i64, usize>,
+    // The numbers a quotient can be multiplied by, before adding or subtracting a remainder.
+    multipliers: Vec<i64>,
+    composed: HashMap<i64, Option<Expression>>,
+}
+
+impl<'a> Composer<'a> {
+    fn new(config: &'a SolverConfig, table: &Solutions) -> Composer<'a> {
+        let mut entries: Vec<(i64, Expression)> = table
+            .iter()
+            .filter_map(|(value, solution)| Some((value, solution.alternatives.first()?.clone())))
+            .collect();
+        entries.sort_by_key(|(value, _)| *value);
+        let lookup = entries
+            .iter()
+            .enumerate()
+            .map(|(index, (value, _))| (*value, index))
+            .collect();
+        Composer {
+            config,
+            table: entries,
+            lookup,
+            multipliers: config.numbers.iter().copied().filter(|n| *n > 1).collect(),
+            composed: HashMap::new(),
+        }
+    }
+
+    fn has(&self, operation: Operation) -> bool {
+        self.config.operations.operations().contains(&operation)
+    }
+
+    fn known(&self, value: i64) -> Option<&Expression> {
+        self.lookup.get(&value).map(|index| &self.table[*index].1)
+    }
+
+    fn compose(&mut self, target: i64) -> Option<Expression> {
+        if let Some(expression) = self.known(target) {
+            return Some(expression.clone());
+        }
+        if target < 2 {
+            return None;
+        }
+        if let Some(composed) = self.composed.get(&target) {
+            return composed.clone();
+        }
+        // A target reached again while being composed, through `quotient + 1`, has no split yet.
+        self.composed.insert(target, None);
+        let mut candidates: Vec<Expression> = vec![];
+        if self.has(Operation::MULT) {
+            // Every value of the table dividing the target, times its cofactor.
+            let factors: Vec<(i64, Expression)> = self
+                .table
+                .iter()
+                .filter(|(value, _)| *value > 1 && *value < target && target % *value == 0)
+                .cloned()
+                .collect();
+            for (factor, expression) in factors {
+                if let Some(cofactor) = self.compose(target / factor) {
+                    candidates.push(Expression::binary(Operation::MULT, expression, cofactor));
+                }
+            }
+            // A multiple of a number, plus or minus a remainder of the table.
+            for multiplier in self.multipliers.clone() {
+                let quotient = target / multiplier;
+                let remainder = target % multiplier;
+                if remainder == 0 || quotient < 1 {
+                    continue;
+                }
+                let number = Expression::Number(multiplier);
+                if self.has(Operation::ADD) {
+                    if let (Some(rest), Some(multiple)) =
+                        (self.known(remainder).cloned(), self.compose(quotient))
+                    {
+                        let multiple =
+                            Expression::binary(Operation::MULT, multiple, number.clone());
+                        candidates.push(Expression::binary(Operation::ADD, multiple, rest));
+                    }
+                }
+                if self.has(Operation::SUB) {
+                    if let (Some(rest), Some(multiple)) = (
+                        self.known(multiplier - remainder).cloned(),
+                        self.compose(quotient + 1),
+                    ) {
+                        let multiple = Expression::binary(Operation::MULT, multiple, number);
+                        candidates.push(Expression::binary(Operation::SUB, multiple, rest));
+                    }
+                }
+            }
+        }
+        // A power of a smaller base, by an exponent of the table.
+        if self.has(Operation::EXP) {
+            for exponent in 2..(target.ilog2() + 1) {
+                let base = (target as f64).powf(1.0 / exponent as f64).round() as i64;
+                if base.checked_pow(exponent) != Some(target) {
+                    continue;
+                }
+                if let (Some(power), Some(base)) =
+                    (self.known(exponent as i64).cloned(), self.compose(base))
+                {
+                    candidates.push(Expression::binary(Operation::EXP, base, power));
+                }
+            }
+        }
+        // The leading digits concatenated with the trailing ones, which cannot start with 0.
+        if self.has(Operation::CONCAT) {
+            let mut shift = 10;
+            while shift <= target {
+                let (leading, trailing) = (target / shift, target % shift);
+                if trailing == 0 && shift == 10 || trailing >= shift / 10 {
+                    if let (Some(leading), Some(trailing)) =
+                        (self.compose(leading), self.compose(trailing))
+                    {
+                        candidates.push(Expression::binary(Operation::CONCAT, leading, trailing));
+                    }
+                }
+                shift = match shift.checked_mul(10) {
+                    Some(shift) => shift,
+                    None => break,
+                };
+            }
+        }
+        let semantics = self.config.semantics;
+        let objective = &self.config.objective;
+        let best = candidates
+            .into_iter()
+            .filter(|candidate| {
+                candidate.eval(semantics) == Ok(target) && self.config.limits.allows(candidate)
+            })
+            .min_by_key(|candidate| (objective.expression_cost(candidate), candidate.size()));
+        self.composed.insert(target, best.clone());
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Solver, SolverConfig, Strategy};
+
+    #[test]
+    fn negative_targets_are_composed() {
+        let config = SolverConfig::builder()
+            .max_number(4)
+            .max_size(2)
+            .strategy(Strategy::Heuristic)
+            .targets(vec![-3])
+            .build()
+            .unwrap();
+        let solutions = Solver::new(config).solve();
+        let solution = solutions.get(-3).unwrap();
+        assert_eq!(solution.size, 2);
+        assert_eq!(solution.alternatives[0].to_string(), "(1-4)");
+    }
+}
@@ -0,0 +1,396 @@
+use rayon::prelude::*;
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+use crate::{
+    dynamic, solver::Deadline, Expression, Operation, Progress, Solution, Solutions, SolverConfig,
+};
+
+// Searches each target on its own with iterative deepening: an expression with at most
+// `threshold` leaves is looked for top-down, splitting the target into a value of the small
+// layers and the operand the other side must then evaluate to, and the threshold grows until
+// one is found. The first threshold with a solution is the minimal size. Operands that cannot
+// be reached with the leaves left, according to the largest magnitude reachable with every
+// number of leaves, are pruned, which is what makes large targets fast.
+//
+// A single minimal alternative is found per target. Unary operations, limits, operation costs
+// and truncating division are not supported by the top-down split, and are searched with the
+// dynamic strategy instead.
+pub(crate) fn search(config: &SolverConfig, progress: &dyn Progress) -> Solutions {
+    if config.targets.is_empty()
+        || !config.operations.unary_operations().is_empty()
+        || config.limits.is_limited()
+        || config.objective.is_weighted()
+        || config.semantics.truncating_division
+    {
+        return dynamic::search(config, progress);
+    }
+    let deadline = config.deadline();
+    let mut layers = Layers::new(config);
+    let mut dictionary = HashMap::new();
+    let mut size_searched = 0;
+    let mut truncated = None;
+    let mut targets = config.targets.clone();
+    targets.sort();
+    targets.dedup();
+    let mut memo = Memo {
+        failed: HashSet::new(),
+        deadline,
+        timed_out: false,
+    };
+    'targets: for target in targets {
+        for threshold in layers.bound(target)..(config.max_size + 1) {
+            progress.size_started(threshold, 0);
+            layers.extend(threshold / 2, config);
+            let found = layers.find(target, threshold, config, &mut memo);
+            progress.explored(memo.failed.len() as u64);
+            if memo.timed_out {
+                truncated = Some((threshold, memo.deadline.interruption()));
+                break 'targets;
+            }
+            progress.size_finished(threshold);
+            size_searched = size_searched.max(threshold);
+            if let Some(expression) = found {
+                let solution = Solution {
+                    size: expression.size(),
+                    cost: config.objective.expression_cost(&expression),
+                    count: 1,
+                    alternatives: vec![expression],
+                    other_sizes: vec![],
+                };
+                progress.solved(target, &solution);
+                dictionary.insert(target, solution);
+                break;
+            }
+        }
+    }
+    let maximum_composed = dictionary.keys().copied().max().unwrap_or(1);
+    Solutions::new(
+        dictionary,
+        maximum_composed,
+        0,
+        size_searched,
+        truncated,
+        false,
+    )
+}
+
+// The values of the small sizes, fully enumerated bottom-up, and a bound of the magnitudes
+// reachable with every size.
+struct Layers {
+    // layers[size] lists the values whose minimal number of leaves is `size`, with one of
+    // their minimal expressions.
+    layers: Vec<Vec<(i64, Expression)>>,
+    known: HashMap<i64, (usize, Expression)>,
+    // reach[size] is no smaller than the magnitude of any value built with `size` leaves.
+    reach: Vec<u64>,
+}
+
+impl Layers {
+    fn new(config: &SolverConfig) -> Layers {
+        let reach = reach(config);
+        let mut layers = Layers {
+            layers: vec![vec![]],
+            known: HashMap::new(),
+            reach,
+        };
+        layers.extend(1, config);
+        layers
+    }
+
+    fn bound(&self, value: i64) -> usize {
+        bound(&self.reach, value)
+    }
+
+    // The number of sizes fully enumerated.
+    fn enumerated(&self) -> usize {
+        self.layers.len() - 1
+    }
+
+    // Enumerates the values of every size up to `size`.
+    fn extend(&mut self, size: usize, config: &SolverConfig) {
+        while self.enumerated() < size {
+            let size = self.layers.len();
+            let layer: Vec<(i64, Expression)> = if size == 1 {
+                config
+                    .numbers
+                    .iter()
+                    .map(|n| (*n, Expression::Number(*n)))
+                    .collect()
+            } else {
+                // The first derivation of every new value, as the split, the operation and the
+                // positions of the operands in their layers.
+                let mut derivations: HashMap<i64, (usize, Operation, usize, usize)> =
+                    HashMap::new();
+                let mut order = vec![];
+                for split in 1..size {
+                    let (lefts, rights) = (&self.layers[split], &self.layers[size - split]);
+                    let known = &self.known;
+                    let combined: Vec<(i64, Operation, usize, usize)> =
+                        lefts
+                            .par_iter()
+                            .enumerate()
+                            .flat_map_iter(|(l, (left, _))| {
+                                rights.iter().enumerate().flat_map(move |(r, (right, _))| {
+                                    config.operations.operations().iter().filter_map(
+                                        move |operation| {
+                                            let value = operation
+                                                .apply(*left, *right, config.semantics)
+                                                .ok()
+                                                .filter(|value| !known.contains_key(value))?;
+                                            Some((value, *operation, l, r))
+                                        },
+                                    )
+                                })
+                            })
+                            .collect();
+                    for (value, operation, l, r) in combined {
+                        derivations.entry(value).or_insert_with(|| {
+                            order.push(value);
+                            (split, operation, l, r)
+                        });
+                    }
+                }
+                order
+                    .into_iter()
+                    .map(|value| {
+                        let (split, operation, l, r) = derivations[&value];
+                        let left = self.layers[split][l].1.clone();
+                        let right = self.layers[size - split][r].1.clone();
+                        (value, Expression::binary(operation, left, right))
+                    })
+                    .collect()
+            };
+            let mut fresh = vec![];
+            for (value, expression) in layer {
+                if let Entry::Vacant(entry) = self.known.entry(value) {
+                    entry.insert((size, expression.clone()));
+                    fresh.push((value, expression));
+                }
+            }
+            self.layers.push(fresh);
+        }
+    }
+
+    // An expression of `target` with at most `leaves` leaves, if there is one.
+    fn find(
+        &self,
+        target: i64,
+        leaves: usize,
+        config: &SolverConfig,
+        memo: &mut Memo,
+    ) -> Option<Expression> {
+        if let Some((size, expression)) = self.known.get(&target) {
+            if *size <= leaves {
+                return Some(expression.clone());
+            }
+        }
+        if leaves <= self.enumerated()
+            || self.bound(target) > leaves
+            || memo.failed.contains(&(target, leaves))
+        {
+            return None;
+        }
+        if memo.expired() {
+            return None;
+        }
+        // The smaller operand of a split has at most half of the leaves. Taking it among the
+        // values of its minimal size leaves as many as possible to the other operand.
+        for size in 1..(leaves / 2 + 1) {
+            for (value, expression) in self.layers[size].iter() {
+                for (operation, small_left, operand) in complements(config, target, *value) {
+                    if self.bound(operand) > leaves - size {
+                        continue;
+                    }
+                    let (left, right) = if small_left {
+                        (*value, operand)
+                    } else {
+                        (operand, *value)
+                    };
+                    if operation.apply(left, right, config.semantics) != Ok(target) {
+                        continue;
+                    }
+                    if let Some(other) = self.find(operand, leaves - size, config, memo) {
+                        let (left, right) = if small_left {
+                            (expression.clone(), other)
+                        } else {
+                            (other, expression.clone())
+                        };
+                        return Some(Expression::binary(operation, left, right));
+                    }
+                    if memo.timed_out {
+                        return None;
+                    }
+                }
+            }
+        }
+        memo.failed.insert((target, leaves));
+        None
+    }
+}
+
+struct Memo {
+    // The targets and numbers of leaves known to have no expression.
+    failed: HashSet<(i64, usize)>,
+    deadline: Deadline,
+    timed_out: bool,
+}
+
+impl Memo {
+    fn expired(&mut self) -> bool {
+        self.timed_out = self.timed_out || self.deadline.expired();
+        self.timed_out
+    }
+}
+
+// reach[size] is no smaller than the magnitude of any value built with `size` leaves, up to the
+// maximum size.
+pub(crate) fn reach(config: &SolverConfig) -> Vec<u64> {
+    let largest = config
+        .numbers
+        .iter()
+        .map(|n| n.unsigned_abs())
+        .max()
+        .unwrap_or(0);
+    let cap = config.semantics.max_intermediate.unwrap_or(u64::MAX);
+    let mut reach = vec![0, largest.min(cap)];
+    for size in 2..(config.max_size + 1) {
+        let bound = (1..size)
+            .flat_map(|split| {
+                let (left, right) = (reach[split], reach[size - split]);
+                config
+                    .operations
+                    .operations()
+                    .iter()
+                    .map(move |operation| magnitude(*operation, left, right))
+            })
+            .max()
+            .unwrap_or(0);
+        reach.push(bound.min(cap));
+    }
+    reach
+}
+
+// The fewest leaves a value can be built with according to the magnitudes, at least 1, or one
+// more than the maximum size if it is out of reach.
+pub(crate) fn bound(reach: &[u64], value: i64) -> usize {
+    let magnitude = value.unsigned_abs();
+    (1..reach.len())
+        .find(|size| reach[*size] >= magnitude)
+        .unwrap_or(reach.len())
+}
+
+// The largest magnitude of an operation applied to operands of magnitude at most `left` and
+// `right`.
+fn magnitude(operation: Operation, left: u64, right: u64) -> u64 {
+    match operation {
+        Operation::ADD | Operation::SUB => left.saturating_add(right),
+        Operation::MULT => left.saturating_mul(right),
+        Operation::DIV => left,
+        Operation::EXP => left.saturating_pow(u32::try_from(right).unwrap_or(u32::MAX)),
+        Operation::CONCAT => 10u64
+            .saturating_pow(right.checked_ilog10().unwrap_or(0) + 1)
+            .saturating_mul(left)
+            .saturating_add(right),
+    }
+}
+
+// The operations and other operands that combined with `small` can evaluate to `target`,
+// `small` being the left operand when the flag is set. The candidates are checked by the
+// caller; an operand that can be anything is taken to be a number, and a few exponents are
+// tried for the bases whose powers repeat.
+pub(crate) fn complements(
+    config: &SolverConfig,
+    target: i64,
+    small: i64,
+) -> Vec<(Operation, bool, i64)> {
+    let any = config.numbers[0];
+    let mut candidates = vec![];
+    for operation in config.operations.operations() {
+        match operation {
+            Operation::ADD => {
+                candidates.extend(target.checked_sub(small).map(|r| (*operation, true, r)))
+            }
+            Operation::SUB => {
+                candidates.extend(small.checked_sub(target).map(|r| (*operation, true, r)));
+                candidates.extend(target.checked_add(small).map(|l| (*operation, false, l)));
+            }
+            Operation::MULT => {
+                if small != 0 && target % small == 0 {
+                    candidates.push((*operation, true, target / small));
+                } else if small == 0 && target == 0 {
+                    candidates.push((*operation, true, any));
+                }
+            }
+            Operation::DIV => {
+                if target != 0 && small % target == 0 {
+                    candidates.push((*operation, true, small / target));
+                } else if target == 0 && small == 0 {
+                    candidates.extend(
+                        config
+                            .numbers
+                            .iter()
+                            .find(|n| **n != 0)
+                            .map(|n| (*operation, true, *n)),
+                    );
+                }
+                if small != 0 {
+                    candidates.extend(target.checked_mul(small).map(|l| (*operation, false, l)));
+                }
+            }
+            Operation::EXP => {
+                // `small` as the base: the exponents up to the magnitude of the target.
+                if small.unsigned_abs() >= 2 {
+                    let mut power = 1i64;
+                    let mut exponent = 0;
+                    while power.unsigned_abs() <= target.unsigned_abs() {
+                        candidates.push((*operation, true, exponent));
+                        exponent += 1;
+                        power = match power.checked_mul(small) {
+                            Some(power) => power,
+                            None => break,
+                        };
+                    }
+                } else {
+                    candidates.extend((0..4).map(|exponent| (*operation, true, exponent)));
+                }
+                // `small` as the exponent: the integer roots of the target.
+                match u32::try_from(small) {
+                    Ok(0) => candidates.push((*operation, false, any)),
+                    Ok(exponent) => {
+                        let root = (target.unsigned_abs() as f64)
+                            .powf(1.0 / exponent as f64)
+                            .round() as i64;
+                        for base in [root - 1, root, root + 1] {
+                            candidates.push((*operation, false, base));
+                            candidates.push((*operation, false, -base));
+                        }
+                    }
+                    Err(_) => {}
+                }
+            }
+            Operation::CONCAT => {
+                if target >= 0 && small >= 0 {
+                    let digits = |n: i64| 10i64.checked_pow(n.checked_ilog10().unwrap_or(0) + 1);
+                    // `small` leading: every trailing part of the target.
+                    let mut shift = 10i64;
+                    while shift <= target {
+                        if target / shift == small {
+                            candidates.push((*operation, true, target % shift));
+                        }
+                        shift = match shift.checked_mul(10) {
+                            Some(shift) => shift,
+                            None => break,
+                        };
+                    }
+                    // `small` trailing: the leading part of the target.
+                    if let Some(shift) = digits(small) {
+                        if target % shift == small {
+                            candidates.push((*operation, false, target / shift));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    candidates
+}
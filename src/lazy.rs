@@ -0,0 +1,167 @@
+#[cfg(feature = "stream")]
+use std::task::Poll;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex},
+    task::Waker,
+    thread,
+};
+
+use crate::{CancellationToken, Progress, Solution, Solver, SolverConfig};
+
+// Solutions waiting to be consumed by an iterator, past which the search waits.
+const CAPACITY: usize = 256;
+
+// The solutions of a search running on its own thread, yielded as soon as they are known, in
+// the order the search reports them as solved. Dropping the iterator cancels the search, so
+// stopping at the value wanted does not pay for the rest of it.
+pub struct SolutionIter {
+    receiver: Receiver,
+}
+
+impl Solver {
+    // Searches on a new thread, iterating over the solutions as they are found.
+    pub fn solutions(&self) -> SolutionIter {
+        SolutionIter {
+            receiver: Receiver::spawn(self.config(), CAPACITY),
+        }
+    }
+}
+
+impl Iterator for SolutionIter {
+    type Item = (i64, Solution);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.next()
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    // Signalled when a solution is consumed, or the receiver dropped.
+    space: Condvar,
+    // Signalled when a solution is queued, or the search done.
+    ready: Condvar,
+}
+
+struct State {
+    queue: VecDeque<(i64, Solution)>,
+    capacity: usize,
+    done: bool,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl State {
+    fn wake(&mut self, shared: &Shared) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+        shared.ready.notify_all();
+    }
+}
+
+// The consuming end of a search running on its own thread, queueing at most `capacity`
+// solutions: past that the search blocks until the consumer catches up.
+pub(crate) struct Receiver {
+    shared: Arc<Shared>,
+    cancellation: CancellationToken,
+}
+
+impl Receiver {
+    pub(crate) fn spawn(config: &SolverConfig, capacity: usize) -> Receiver {
+        let mut config = config.clone();
+        let cancellation = config
+            .cancellation
+            .get_or_insert_with(CancellationToken::new)
+            .clone();
+        let solver = Solver::new(config);
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                capacity: capacity.max(1),
+                done: false,
+                closed: false,
+                waker: None,
+            }),
+            space: Condvar::new(),
+            ready: Condvar::new(),
+        });
+        let sender = Sender(shared.clone());
+        thread::spawn(move || {
+            solver.solve_with_progress(&sender);
+            let mut state = sender.0.state.lock().unwrap();
+            state.done = true;
+            state.wake(&sender.0);
+        });
+        Receiver {
+            shared,
+            cancellation,
+        }
+    }
+
+    // The next solution, waiting for the search to find it.
+    pub(crate) fn next(&self) -> Option<(i64, Solution)> {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(item) = state.queue.pop_front() {
+                self.shared.space.notify_one();
+                return Some(item);
+            }
+            if state.done {
+                return None;
+            }
+            state = self.shared.ready.wait(state).unwrap();
+        }
+    }
+
+    // The next solution if already found, or else the waker is woken once it is.
+    #[cfg(feature = "stream")]
+    pub(crate) fn poll_next(&self, waker: &Waker) -> Poll<Option<(i64, Solution)>> {
+        let mut state = self.shared.state.lock().unwrap();
+        if let Some(item) = state.queue.pop_front() {
+            self.shared.space.notify_one();
+            return Poll::Ready(Some(item));
+        }
+        if state.done {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(waker.clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        self.cancellation.cancel();
+        let mut state = self.shared.state.lock().unwrap();
+        state.closed = true;
+        state.queue.clear();
+        self.shared.space.notify_all();
+    }
+}
+
+// Queues the solutions reported by the search for the receiver.
+struct Sender(Arc<Shared>);
+
+impl Progress for Sender {
+    fn size_started(&self, _size: usize, _total: u64) {}
+
+    fn explored(&self, _candidates: u64) {}
+
+    fn discovered(&self, _values: usize) {}
+
+    fn solved(&self, value: i64, solution: &Solution) {
+        let mut state = self.0.state.lock().unwrap();
+        while state.queue.len() >= state.capacity && !state.closed {
+            state = self.0.space.wait(state).unwrap();
+        }
+        if state.closed {
+            return;
+        }
+        state.queue.push_back((value, solution.clone()));
+        state.wake(&self.0);
+    }
+
+    fn size_finished(&self, _size: usize) {}
+}
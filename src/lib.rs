@@ -0,0 +1,50 @@
+mod brute_force;
+mod builder;
+mod checkpoint;
+mod dynamic;
+#[cfg(any(feature = "wasm", feature = "ffi"))]
+mod embedded;
+mod expression;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod heuristic;
+mod ida;
+mod lazy;
+mod limits;
+mod meet_in_the_middle;
+mod minimality;
+mod objective;
+mod operation;
+mod parser;
+mod plan;
+mod progress;
+mod rates;
+mod render;
+mod solver;
+#[cfg(feature = "stream")]
+mod stream;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+pub use builder::{ConfigError, SolverConfigBuilder};
+pub use checkpoint::{Checkpoint, CheckpointError, CheckpointPolicy};
+pub use expression::Expression;
+#[cfg(feature = "ffi")]
+pub use ffi::{beltmatic_free, beltmatic_solve};
+pub use lazy::SolutionIter;
+pub use limits::Limits;
+pub use minimality::MinimalSizes;
+pub use objective::Objective;
+pub use operation::{EvalError, Operation, OperationDictionary, Semantics, UnaryOperation, Width};
+pub use parser::ParseError;
+pub use plan::Plan;
+pub use progress::{NoProgress, Progress, SolverObserver};
+pub use rates::Rates;
+pub use render::Step;
+pub use solver::{
+    CancellationToken, Interruption, Solution, Solutions, Solver, SolverConfig, Strategy,
+};
+#[cfg(feature = "stream")]
+pub use stream::SolutionStream;
+#[cfg(feature = "wasm")]
+pub use wasm::solve;
@@ -0,0 +1,335 @@
+use std::{collections::HashMap, ops::Range};
+
+use crate::{Expression, Operation, SolverConfig, UnaryOperation};
+
+// The maximum number of times each operation, and each number, can appear in an expression.
+// Operations and numbers not listed can be used any number of times. Pairs of operations can
+// also be forbidden from feeding into each other, numbers required to appear, the depth of the
+// expressions bounded and their shape restricted to chains.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    uses: HashMap<Operation, usize>,
+    unary_uses: HashMap<UnaryOperation, usize>,
+    sources: HashMap<i64, usize>,
+    // The result of the first operation of a pair cannot be an operand of the second.
+    forbidden_adjacent: Vec<(Operation, Operation)>,
+    // Numbers that every expression must use at least once.
+    required: Vec<i64>,
+    // The most operations on the way from a leaf to the root, each adding a machine in series.
+    max_depth: Option<usize>,
+    // Every operation takes the result of the previous one on the left and a number on the
+    // right, like `((a+b)*c)-d`.
+    chains_only: bool,
+}
+
+impl Limits {
+    // The number of operations and numbers that can be limited in a search.
+    pub const MAX_LIMITED: usize = (Uses::BITS / FIELD_BITS) as usize;
+
+    pub fn new(
+        uses: HashMap<Operation, usize>,
+        unary_uses: HashMap<UnaryOperation, usize>,
+        sources: HashMap<i64, usize>,
+    ) -> Limits {
+        Limits {
+            uses,
+            unary_uses,
+            sources,
+            forbidden_adjacent: vec![],
+            required: vec![],
+            max_depth: None,
+            chains_only: false,
+        }
+    }
+
+    // Forbids the result of the first operation of every pair from being directly an operand
+    // of the second. A unary operation in between separates them.
+    pub fn forbid_adjacent(mut self, pairs: Vec<(Operation, Operation)>) -> Limits {
+        self.forbidden_adjacent = pairs;
+        self
+    }
+
+    // Requires every expression to use each of the numbers at least once.
+    pub fn require(mut self, numbers: Vec<i64>) -> Limits {
+        self.required = numbers;
+        self
+    }
+
+    // Bounds the depth of every expression, leaves having a depth of 0.
+    pub fn limit_depth(mut self, max_depth: Option<usize>) -> Limits {
+        self.max_depth = max_depth;
+        self
+    }
+
+    // Restricts the expressions to left-deep chains, whose right operands are all numbers.
+    pub fn restrict_to_chains(mut self, chains_only: bool) -> Limits {
+        self.chains_only = chains_only;
+        self
+    }
+
+    pub fn is_limited(&self) -> bool {
+        !self.uses.is_empty()
+            || !self.unary_uses.is_empty()
+            || !self.sources.is_empty()
+            || !self.forbidden_adjacent.is_empty()
+            || !self.required.is_empty()
+            || self.max_depth.is_some()
+            || self.chains_only
+    }
+
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    pub fn chains_only(&self) -> bool {
+        self.chains_only
+    }
+
+    // The numbers of leaves the left operand of an expression with `size` leaves can have.
+    pub(crate) fn splits(&self, size: usize) -> Range<usize> {
+        if self.chains_only {
+            size.saturating_sub(1).max(1)..size
+        } else {
+            1..size
+        }
+    }
+
+    pub fn required(&self) -> &[i64] {
+        &self.required
+    }
+
+    pub fn forbidden_adjacent(&self) -> &[(Operation, Operation)] {
+        &self.forbidden_adjacent
+    }
+
+    pub fn forbids(&self, producer: Operation, consumer: Operation) -> bool {
+        self.forbidden_adjacent.contains(&(producer, consumer))
+    }
+
+    pub fn max_uses(&self, operation: Operation) -> Option<usize> {
+        self.uses.get(&operation).copied()
+    }
+
+    pub fn max_unary_uses(&self, operation: UnaryOperation) -> Option<usize> {
+        self.unary_uses.get(&operation).copied()
+    }
+
+    // The numbers whose leaves are limited.
+    pub fn limited_numbers(&self) -> impl Iterator<Item = i64> + '_ {
+        self.sources.keys().copied()
+    }
+
+    // The maximum number of leaves of the given number, each needing an extractor of it.
+    pub fn max_source_uses(&self, number: i64) -> Option<usize> {
+        self.sources.get(&number).copied()
+    }
+
+    // Whether the expression uses every operation and number no more than allowed and the
+    // required numbers, is not deeper than allowed and is a chain if required.
+    pub fn allows(&self, expression: &Expression) -> bool {
+        self.uses
+            .iter()
+            .all(|(operation, max)| expression.uses(*operation) <= *max)
+            && self
+                .unary_uses
+                .iter()
+                .all(|(operation, max)| expression.unary_uses(*operation) <= *max)
+            && self
+                .sources
+                .iter()
+                .all(|(number, max)| expression.number_uses(*number) <= *max)
+            && self.adjacent_allowed(expression, None)
+            && self
+                .required
+                .iter()
+                .all(|number| expression.number_uses(*number) > 0)
+            && self
+                .max_depth
+                .is_none_or(|max_depth| expression.depth() <= max_depth)
+            && (!self.chains_only || expression.is_chain())
+    }
+
+    // Whether no operation of the expression feeds into one it is forbidden to, `consumer`
+    // being the operation the expression is an operand of.
+    fn adjacent_allowed(&self, expression: &Expression, consumer: Option<Operation>) -> bool {
+        match expression {
+            Expression::Number(_) => true,
+            Expression::Binary(operation, left, right) => {
+                consumer.is_none_or(|consumer| !self.forbids(*operation, consumer))
+                    && self.adjacent_allowed(left, Some(*operation))
+                    && self.adjacent_allowed(right, Some(*operation))
+            }
+            Expression::Unary(_, operand) => self.adjacent_allowed(operand, None),
+        }
+    }
+}
+
+// Counts of the limited operations and numbers used by an expression, packed in fields of
+// `FIELD_BITS` bits so that two counts can be added without carrying into the next field.
+pub(crate) type Uses = u64;
+
+// The operation at the root of an expression, as one plus its index in the dictionary, when it
+// is forbidden from feeding into some operation, and 0 otherwise.
+pub(crate) type Root = u8;
+
+const FIELD_BITS: u32 = 9;
+const MAX_LIMIT: usize = (1 << (FIELD_BITS - 1)) - 1;
+
+// The fields of the operations of a dictionary, in the order of the dictionary, then those of
+// the numbers, then the depth. The depth of two operands combined is the largest of theirs, and
+// grows by one with the operation combining them.
+pub(crate) struct Usage {
+    binary: Vec<Uses>,
+    unary: Vec<Uses>,
+    numbers: HashMap<i64, Uses>,
+    limits: Vec<u64>,
+    // The fields of the required numbers, and those of them only telling whether the number
+    // is used, which are kept at 1 so that the uses of a value do not multiply.
+    required: Vec<usize>,
+    presence: Vec<usize>,
+    depth: Option<usize>,
+    // feeds[root][index] tells whether an expression with the root can be an operand of the
+    // `index`-th binary operation.
+    feeds: Vec<Vec<bool>>,
+}
+
+impl Usage {
+    pub(crate) fn new(config: &SolverConfig) -> Usage {
+        let (limits, operations) = (&config.limits, &config.operations);
+        let mut fields = vec![];
+        let field = |fields: &mut Vec<u64>, limit: Option<usize>| match limit {
+            Some(limit) => {
+                let unit = 1 << (FIELD_BITS * fields.len() as u32);
+                fields.push(limit.min(MAX_LIMIT) as u64);
+                unit
+            }
+            None => 0,
+        };
+        let binary = operations
+            .operations()
+            .iter()
+            .map(|operation| field(&mut fields, limits.max_uses(*operation)))
+            .collect();
+        let unary = operations
+            .unary_operations()
+            .iter()
+            .map(|operation| field(&mut fields, limits.max_unary_uses(*operation)))
+            .collect();
+        // A required number without a limit gets a field telling whether it is used.
+        let mut required = vec![];
+        let mut presence = vec![];
+        let numbers = config
+            .numbers
+            .iter()
+            .map(|number| {
+                let max = limits.max_source_uses(*number);
+                if !limits.required().contains(number) {
+                    return (*number, field(&mut fields, max));
+                }
+                if max.is_none() {
+                    presence.push(fields.len());
+                }
+                required.push(fields.len());
+                (*number, field(&mut fields, max.or(Some(MAX_LIMIT))))
+            })
+            .collect();
+        let depth = limits.max_depth().map(|max_depth| {
+            field(&mut fields, Some(max_depth));
+            fields.len() - 1
+        });
+        assert!(
+            fields.len() as u32 * FIELD_BITS <= Uses::BITS,
+            "too many limited operations and numbers"
+        );
+        let feeds = std::iter::once(None)
+            .chain(operations.operations().iter().map(Some))
+            .map(|producer| {
+                operations
+                    .operations()
+                    .iter()
+                    .map(|consumer| producer.is_none_or(|p| !limits.forbids(*p, *consumer)))
+                    .collect()
+            })
+            .collect();
+        Usage {
+            binary,
+            unary,
+            numbers,
+            limits: fields,
+            required,
+            presence,
+            depth,
+            feeds,
+        }
+    }
+
+    // The root of an expression of the `index`-th binary operation.
+    pub(crate) fn root(&self, index: usize) -> Root {
+        if self.feeds[index + 1].iter().all(|feeds| *feeds) {
+            0
+        } else {
+            index as Root + 1
+        }
+    }
+
+    // Whether an expression with the root can be an operand of the `index`-th binary operation.
+    pub(crate) fn feeds(&self, root: Root, index: usize) -> bool {
+        self.feeds[root as usize][index]
+    }
+
+    // The uses of the `index`-th binary operation of the dictionary.
+    pub(crate) fn binary(&self, index: usize) -> Uses {
+        self.binary[index]
+    }
+
+    pub(crate) fn unary(&self, index: usize) -> Uses {
+        self.unary[index]
+    }
+
+    // The uses of a leaf of the given number, unless it cannot be used at all.
+    pub(crate) fn number(&self, number: i64) -> Option<Uses> {
+        self.combine(0, self.numbers.get(&number).copied().unwrap_or(0))
+    }
+
+    // The uses of two subexpressions combined, unless they exceed a limit.
+    pub(crate) fn combine(&self, left: Uses, right: Uses) -> Option<Uses> {
+        let mut uses = left + right;
+        if let Some(i) = self.depth {
+            uses -= Self::field(left, i).min(Self::field(right, i)) << (FIELD_BITS * i as u32);
+        }
+        if !self
+            .limits
+            .iter()
+            .enumerate()
+            .all(|(i, limit)| Self::field(uses, i) <= *limit)
+        {
+            return None;
+        }
+        for &i in self.presence.iter() {
+            if Self::field(uses, i) > 1 {
+                uses -= (Self::field(uses, i) - 1) << (FIELD_BITS * i as u32);
+            }
+        }
+        Some(uses)
+    }
+
+    // The uses of an expression combined by one more operation, unless it gets too deep.
+    pub(crate) fn deepen(&self, uses: Uses) -> Option<Uses> {
+        match self.depth {
+            Some(i) => {
+                let uses = uses + (1 << (FIELD_BITS * i as u32));
+                (Self::field(uses, i) <= self.limits[i]).then_some(uses)
+            }
+            None => Some(uses),
+        }
+    }
+
+    // Whether the uses include every required number.
+    pub(crate) fn satisfies(&self, uses: Uses) -> bool {
+        self.required.iter().all(|i| Self::field(uses, *i) > 0)
+    }
+
+    fn field(uses: Uses, i: usize) -> u64 {
+        (uses >> (FIELD_BITS * i as u32)) & ((1 << FIELD_BITS) - 1)
+    }
+}
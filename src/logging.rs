@@ -0,0 +1,92 @@
+use beltmatic_calc::{Progress, Solution};
+use clap::ValueEnum;
+use std::{
+    io::IsTerminal,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+use tracing::{debug, trace, Level};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub(crate) enum LogFormat {
+    Text,
+    Json,
+}
+
+// Diagnostics are written to standard error, with the events of every size from `-v` and those
+// of every value solved from `-vv`. Without `-v` the text lines only carry the message.
+pub(crate) fn init(verbose: u8, format: LogFormat) {
+    let level = match verbose {
+        0 => Level::INFO,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_target(false);
+    match format {
+        LogFormat::Text if verbose == 0 => subscriber.without_time().with_level(false).init(),
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+}
+
+// Emits the events of the search while `progress` follows it.
+pub(crate) struct Logged {
+    progress: Box<dyn Progress>,
+    started: Mutex<Instant>,
+    values: AtomicUsize,
+}
+
+impl Logged {
+    pub(crate) fn new(progress: Box<dyn Progress>) -> Logged {
+        Logged {
+            progress,
+            started: Mutex::new(Instant::now()),
+            values: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Progress for Logged {
+    fn size_started(&self, size: usize, total: u64) {
+        *self.started.lock().unwrap() = Instant::now();
+        debug!(size, candidates = total, "size started");
+        self.progress.size_started(size, total);
+    }
+
+    fn explored(&self, candidates: u64) {
+        self.progress.explored(candidates);
+    }
+
+    fn discovered(&self, values: usize) {
+        self.values.store(values, Ordering::Relaxed);
+        self.progress.discovered(values);
+    }
+
+    fn solved(&self, value: i64, solution: &Solution) {
+        trace!(
+            value,
+            size = solution.size,
+            cost = solution.cost,
+            "value solved"
+        );
+        self.progress.solved(value, solution);
+    }
+
+    fn size_finished(&self, size: usize) {
+        let elapsed = self.started.lock().unwrap().elapsed();
+        debug!(
+            size,
+            values = self.values.load(Ordering::Relaxed),
+            elapsed_ms = elapsed.as_millis() as u64,
+            "size finished"
+        );
+        self.progress.size_finished(size);
+    }
+}
@@ -1,310 +1,2408 @@
-use clap::Parser;
-use std::{cell::RefCell, collections::HashMap, fmt::Display, process::exit, rc::Rc};
+mod analyze;
+mod chart;
+mod config;
+mod export;
+mod failure;
+mod logging;
+mod metrics;
+mod query;
+mod reachability;
+mod repl;
+mod serve;
+mod stats;
+mod template;
+mod throughput;
+mod verify;
 
-#[derive(PartialEq, Eq, Clone, Copy, Hash)]
-enum Operation {
-    ADD,
-    MULT,
-    DIV,
-    SUB,
+use beltmatic_calc::{
+    Checkpoint, CheckpointPolicy, Expression, Interruption, Limits, MinimalSizes, NoProgress,
+    Objective, Operation, Plan, Progress, Semantics, Solution, Solutions, Solver, SolverConfig,
+    SolverConfigBuilder, Strategy, UnaryOperation, Width,
+};
+use clap::{ArgAction, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use export::Export;
+use failure::{fail, ErrorFormat, Failure};
+use indicatif::{ProgressBar, ProgressStyle};
+use logging::{LogFormat, Logged};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    ffi::OsString,
+    fs::{self, File},
+    io::{BufWriter, Stdout, Write},
+    net::SocketAddr,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    process::exit,
+    str::FromStr,
+    sync::Mutex,
+    time::Duration,
+};
+use template::Template;
+use tracing::{info, warn, Level};
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum SolverKind {
+    BruteForce,
+    Dp,
+    Heuristic,
+    Ida,
+    MeetInTheMiddle,
 }
 
-#[derive(Parser, Debug)]
-struct Args {
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum WidthArg {
+    #[value(name = "32")]
+    Bits32,
+    #[value(name = "64")]
+    Bits64,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    Text,
+    Json,
+    Csv,
+    Tsv,
+    Dot,
+    Latex,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Syntax {
+    Infix,
+    Rpn,
+    Steps,
+    Tree,
+    Call,
+    Sexpr,
+}
+
+impl Syntax {
+    // Trees span several lines and are only drawn by the text format, the other formats fall
+    // back to the infix notation.
+    fn render(&self, expression: &Expression, semantics: Semantics) -> String {
+        self.render_as(expression, semantics, false)
+    }
+
+    // With `pretty` the infix notation and the steps are written with the symbols of
+    // mathematics, the other syntaxes keep theirs.
+    fn render_as(&self, expression: &Expression, semantics: Semantics, pretty: bool) -> String {
+        let infix = |expression: &Expression| {
+            if pretty {
+                expression.pretty()
+            } else {
+                expression.to_string()
+            }
+        };
+        match self {
+            Syntax::Infix | Syntax::Tree => infix(expression),
+            Syntax::Rpn => expression.rpn(),
+            Syntax::Call => expression.call(),
+            Syntax::Sexpr => expression.sexpr(),
+            Syntax::Steps => match expression.steps(semantics) {
+                Ok(steps) if steps.is_empty() => infix(expression),
+                Ok(steps) => steps
+                    .iter()
+                    .enumerate()
+                    .map(|(i, step)| {
+                        if pretty {
+                            format!("step {}: {}", i + 1, step.pretty())
+                        } else {
+                            format!("step {}: {}", i + 1, step)
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .join("; "),
+                Err(e) => e.to_string(),
+            },
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Sort {
+    Value,
+    Size,
+    Alternatives,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Report {
+    Solutions,
+    Missing,
+    Richness,
+    Hardest,
+}
+
+fn semantics(
+    width: WidthArg,
+    truncating_division: bool,
+    max_intermediate: Option<u64>,
+    no_negative_intermediates: bool,
+) -> Semantics {
+    Semantics {
+        width: match width {
+            WidthArg::Bits32 => Width::Bits32,
+            WidthArg::Bits64 => Width::Bits64,
+        },
+        truncating_division,
+        max_intermediate,
+        no_negative_intermediates,
+    }
+}
+
+#[derive(Args, Debug)]
+struct SearchArgs {
+    #[arg(long, required_unless_present = "numbers", conflicts_with = "numbers")]
+    max_number: Option<i64>,
     #[arg(long)]
-    max_number: i32,
+    numbers: Option<String>,
     #[arg(long)]
-    max_size: usize,
+    have: Option<String>,
+    #[arg(long)]
+    exclude: Option<String>,
+    #[arg(long, required_unless_present = "until_covered")]
+    max_size: Option<usize>,
+    #[arg(long)]
+    until_covered: bool,
+    #[arg(long)]
+    coverage: Option<f64>,
     #[arg(long)]
+    max_depth: Option<usize>,
+    #[arg(long)]
+    chain_only: bool,
+    #[arg(long, allow_hyphen_values = true)]
     operations: Option<String>,
+    #[arg(long, value_enum, default_value_t = SolverKind::BruteForce)]
+    solver: SolverKind,
+    #[arg(long)]
+    threads: Option<usize>,
+    #[arg(long, value_enum, default_value_t = WidthArg::Bits32)]
+    width: WidthArg,
+    #[arg(long)]
+    allow_truncating_division: bool,
+    #[arg(long)]
+    max_intermediate: Option<u64>,
+    #[arg(long)]
+    no_negative_intermediates: bool,
+    #[arg(long)]
+    op_cost: Option<String>,
+    #[arg(long)]
+    cost_model: Option<PathBuf>,
+    #[arg(long)]
+    max_uses: Option<String>,
+    #[arg(long)]
+    source_limit: Option<String>,
+    #[arg(long)]
+    forbid_adjacent: Option<String>,
+    #[arg(long)]
+    must_use: Option<String>,
+    #[arg(long)]
+    top_k: Option<usize>,
+    #[arg(long, requires = "top_k", conflicts_with_all = ["until_covered", "coverage"])]
+    all_sizes: bool,
+    #[arg(long)]
+    dedupe: bool,
+    #[arg(long, allow_hyphen_values = true)]
+    prefer: Option<String>,
+    #[arg(long)]
+    prefer_few_sources: bool,
+    #[arg(long)]
+    prefer_shallow: bool,
+    #[arg(long)]
+    timeout: Option<u64>,
+    #[arg(long)]
+    max_memory: Option<usize>,
+    #[arg(long)]
+    no_progress: bool,
 }
 
-impl Display for Operation {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::ADD => write!(f, "+"),
-            Self::SUB => write!(f, "-"),
-            Self::MULT => write!(f, "*"),
-            Self::DIV => write!(f, "/"),
+#[derive(Args, Debug)]
+struct OutputArgs {
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    #[arg(long, value_enum, default_value_t = Syntax::Infix)]
+    syntax: Syntax,
+    #[arg(long)]
+    output: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t = Report::Solutions)]
+    report: Report,
+    #[arg(long)]
+    group_ranges: bool,
+    #[arg(long, value_enum)]
+    sort: Option<Sort>,
+    #[arg(long)]
+    only_unsolved: bool,
+    #[arg(long, conflicts_with_all = ["format", "output", "report", "sort", "prove_minimal", "difficulty", "export", "group_equivalent", "pretty", "only_unsolved"])]
+    stream: bool,
+    #[arg(long)]
+    prove_minimal: bool,
+    #[arg(long)]
+    machines: bool,
+    #[arg(long)]
+    difficulty: bool,
+    #[arg(long)]
+    group_equivalent: bool,
+    #[arg(long)]
+    order_operands: bool,
+    #[arg(long)]
+    pretty: bool,
+    #[arg(long)]
+    export: Option<String>,
+    #[arg(long)]
+    template: Option<String>,
+    #[arg(long, default_value_t = 10)]
+    hardest: usize,
+}
+
+#[derive(Args, Debug)]
+struct CheckpointArgs {
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+    #[arg(long, default_value_t = 60)]
+    checkpoint_interval: u64,
+    #[arg(long)]
+    resume: Option<PathBuf>,
+    #[arg(long, conflicts_with_all = ["checkpoint", "resume"])]
+    cache: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct EnumerateArgs {
+    #[command(flatten)]
+    search: SearchArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    #[command(flatten)]
+    checkpoints: CheckpointArgs,
+    #[arg(long)]
+    print_from: Option<i64>,
+    #[arg(long)]
+    print_to: Option<i64>,
+    #[arg(long, conflicts_with_all = ["until_covered", "coverage", "all_sizes", "stream", "report", "sort", "export", "template", "checkpoint", "resume", "cache"])]
+    count_only: bool,
+}
+
+#[derive(Args, Debug)]
+struct SolveArgs {
+    #[arg(
+        required_unless_present = "targets_file",
+        allow_negative_numbers = true
+    )]
+    targets: Vec<i64>,
+    #[arg(long, conflicts_with = "targets")]
+    targets_file: Option<PathBuf>,
+    #[command(flatten)]
+    search: SearchArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    #[command(flatten)]
+    checkpoints: CheckpointArgs,
+}
+
+#[derive(Args, Debug)]
+struct PlanArgs {
+    #[arg(
+        required_unless_present = "targets_file",
+        allow_negative_numbers = true
+    )]
+    targets: Vec<i64>,
+    #[arg(long, conflicts_with = "targets")]
+    targets_file: Option<PathBuf>,
+    #[command(flatten)]
+    search: SearchArgs,
+    #[command(flatten)]
+    checkpoints: CheckpointArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Search every value and print the solutions from 1 to the largest value composed
+    Enumerate(EnumerateArgs),
+    /// Search only the given targets and print their solutions
+    Solve(SolveArgs),
+    /// Search the given targets and choose their alternatives to share the most machines
+    Plan(PlanArgs),
+    /// Evaluate an expression, e.g. `(3*7+2)*11`, and check it against a target
+    Verify(VerifyArgs),
+    /// Compute the items per second delivered by expressions, given the rates of the machines
+    Throughput(ThroughputArgs),
+    /// Find the slowest machines of an expression and the branch to duplicate to balance it
+    Analyze(AnalyzeArgs),
+    /// Search once, then answer `GET /solve?target=N&max_size=K` and the requests of `/openapi.json`
+    Serve(ServeArgs),
+    /// Search every value and print statistics about the values found
+    Stats(StatsArgs),
+    /// Search once, then answer queries for the values typed on standard input
+    Repl(SearchArgs),
+    /// Print the solutions of values from a database written by `--export`, without searching
+    Query(QueryArgs),
+    /// Print the completion script of the given shell
+    Completions(CompletionsArgs),
+}
+
+#[derive(Args, Debug)]
+struct QueryArgs {
+    #[arg(required_unless_present = "range", allow_negative_numbers = true)]
+    values: Vec<i64>,
+    #[arg(long)]
+    database: PathBuf,
+    #[arg(long, allow_hyphen_values = true)]
+    range: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct CompletionsArgs {
+    #[arg(value_enum)]
+    shell: Shell,
+}
+
+#[derive(Args, Debug)]
+struct VerifyArgs {
+    #[arg(allow_hyphen_values = true)]
+    expression: String,
+    #[arg(long)]
+    target: Option<i64>,
+    #[arg(long, value_enum, default_value_t = WidthArg::Bits32)]
+    width: WidthArg,
+    #[arg(long)]
+    allow_truncating_division: bool,
+    #[arg(long)]
+    max_intermediate: Option<u64>,
+    #[arg(long)]
+    no_negative_intermediates: bool,
+    #[arg(long)]
+    op_cost: Option<String>,
+    #[arg(long)]
+    cost_model: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct ThroughputArgs {
+    #[arg(required = true)]
+    expressions: Vec<String>,
+    #[arg(long, default_value_t = 1.0)]
+    extractor_rate: f64,
+    #[arg(long)]
+    op_rate: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct AnalyzeArgs {
+    #[arg(allow_hyphen_values = true)]
+    expression: String,
+    #[arg(long, default_value_t = 1.0)]
+    extractor_rate: f64,
+    #[arg(long)]
+    op_rate: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct StatsArgs {
+    #[command(flatten)]
+    search: SearchArgs,
+    #[arg(long)]
+    chart: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct ServeArgs {
+    #[command(flatten)]
+    search: SearchArgs,
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    bind: SocketAddr,
+    #[arg(long)]
+    max_job_size: Option<usize>,
+    #[arg(long, default_value_t = 2)]
+    max_jobs: usize,
+    #[arg(long, default_value_t = 3600)]
+    job_ttl: u64,
+}
+
+#[derive(Parser, Debug)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+    #[arg(long, value_enum, default_value_t = LogFormat::Text, global = true)]
+    log_format: LogFormat,
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Text, global = true)]
+    error_format: ErrorFormat,
+}
+
+// Draws one progress bar per size on standard error, hidden when it is not a terminal.
+struct ProgressBars {
+    bar: Mutex<ProgressBar>,
+}
+
+impl ProgressBars {
+    fn new() -> ProgressBars {
+        ProgressBars {
+            bar: Mutex::new(ProgressBar::hidden()),
         }
     }
 }
 
-trait Node: Display {
-    fn eval(&self) -> Option<i32>;
+impl Progress for ProgressBars {
+    fn size_started(&self, size: usize, total: u64) {
+        let bar = ProgressBar::new(total)
+            .with_prefix(format!("size {}", size))
+            .with_style(
+                ProgressStyle::with_template(
+                    "{prefix} [{bar:40}] {human_pos}/{human_len} candidates, {msg} values found, ETA {eta}",
+                )
+                .unwrap()
+                .progress_chars("=> "),
+            );
+        let mut current = self.bar.lock().unwrap();
+        bar.set_message(current.message());
+        *current = bar;
+    }
+
+    fn explored(&self, candidates: u64) {
+        self.bar.lock().unwrap().inc(candidates);
+    }
+
+    fn discovered(&self, values: usize) {
+        self.bar.lock().unwrap().set_message(values.to_string());
+    }
+
+    fn solved(&self, _value: i64, _solution: &Solution) {}
+
+    fn size_finished(&self, _size: usize) {
+        self.bar.lock().unwrap().finish();
+    }
 }
 
-struct NumNode {
-    value: Rc<RefCell<i32>>,
+// Prints the solved values of `range` to standard output as JSON lines while `progress` follows
+// the search.
+struct Stream {
+    progress: Box<dyn Progress>,
+    range: RangeInclusive<i64>,
+    syntax: Syntax,
+    semantics: Semantics,
+    machines: bool,
+    order_operands: bool,
+    template: Option<Template>,
+    out: Mutex<Stdout>,
 }
 
-impl Display for NumNode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.value.as_ref().borrow())
+impl Progress for Stream {
+    fn size_started(&self, size: usize, total: u64) {
+        self.progress.size_started(size, total);
+    }
+
+    fn explored(&self, candidates: u64) {
+        self.progress.explored(candidates);
+    }
+
+    fn discovered(&self, values: usize) {
+        self.progress.discovered(values);
+    }
+
+    fn solved(&self, value: i64, solution: &Solution) {
+        if !self.range.contains(&value) {
+            return;
+        }
+        let ordered = self.order_operands.then(|| {
+            let mut solution = solution.clone();
+            solution.order_operands(self.semantics);
+            solution
+        });
+        let solution = ordered.as_ref().unwrap_or(solution);
+        let mut out = self.out.lock().unwrap();
+        let result = match &self.template {
+            Some(template) => writeln!(
+                out,
+                "{}",
+                template.render(
+                    value,
+                    Some(solution),
+                    self.syntax,
+                    self.semantics,
+                    &Annotations::default()
+                )
+            ),
+            None => {
+                let mut line = JsonValue::new(value, Some(solution), self.syntax, self.semantics);
+                if self.machines {
+                    line.machines = Some(json_machines(Some(solution)));
+                }
+                serde_json::to_writer(&mut *out, &line)
+                    .map_err(std::io::Error::from)
+                    .and_then(|_| writeln!(out))
+            }
+        }
+        .and_then(|_| out.flush());
+        if let Err(e) = result {
+            fail(Failure::Io, format!("unable to write results: {}", e));
+        }
+    }
+
+    fn size_finished(&self, size: usize) {
+        self.progress.size_finished(size);
     }
 }
 
-impl Node for NumNode {
-    fn eval(&self) -> Option<i32> {
-        Some(*self.value.as_ref().borrow())
+fn print_text(
+    out: &mut dyn Write,
+    solutions: &Solutions,
+    printed: impl Iterator<Item = i64>,
+    show_cost: bool,
+    syntax: Syntax,
+    semantics: Semantics,
+    annotations: &Annotations,
+) -> std::io::Result<()> {
+    let render = |alternative: &Expression| annotations.render(syntax, alternative, semantics);
+    for v in printed {
+        let solution = match solutions.get(v) {
+            Some(solution) => solution,
+            None => {
+                writeln!(out, "{} -> None", v)?;
+                continue;
+            }
+        };
+        let mut size = if show_cost {
+            format!("{}, cost {}", solution.size, solution.cost)
+        } else {
+            solution.size.to_string()
+        };
+        if let Some(difficulty) = difficulty(annotations, v, Some(solution)) {
+            size.push_str(&format!(", difficulty {}", difficulty));
+        }
+        match minimal(annotations.proof.as_ref(), v, solution, semantics) {
+            Some(true) => size.push_str(", minimal"),
+            Some(false) => size.push_str(", not minimal"),
+            None => {}
+        }
+        let alternatives = if annotations.group {
+            group(&solution.alternatives)
+        } else {
+            solution
+                .alternatives
+                .iter()
+                .map(|alternative| (alternative, 1))
+                .collect()
+        };
+        match syntax {
+            Syntax::Infix | Syntax::Rpn | Syntax::Steps | Syntax::Call | Syntax::Sexpr => {
+                writeln!(
+                    out,
+                    "{} -> ({}) {:?}",
+                    v,
+                    size,
+                    alternatives
+                        .iter()
+                        .map(|(alternative, variants)| {
+                            format!("{}{}", render(alternative), suffix(*variants))
+                        })
+                        .collect::<Vec<String>>()
+                )?;
+                if annotations.machines {
+                    for (alternative, _) in &alternatives {
+                        writeln!(out, "  {}: {}", render(alternative), machines(alternative))?;
+                    }
+                }
+            }
+            Syntax::Tree => {
+                writeln!(out, "{} -> ({})", v, size)?;
+                for (alternative, variants) in &alternatives {
+                    for line in alternative.tree(semantics).lines() {
+                        writeln!(out, "  {}", line)?;
+                    }
+                    if *variants > 1 {
+                        writeln!(out, " {}", suffix(*variants))?;
+                    }
+                    if annotations.machines {
+                        writeln!(out, "  {}", machines(alternative))?;
+                    }
+                }
+            }
+        }
+        for other in &solution.other_sizes {
+            let size = if show_cost {
+                format!("size {}, cost {}", other.size, other.cost)
+            } else {
+                format!("size {}", other.size)
+            };
+            match syntax {
+                Syntax::Tree => {
+                    writeln!(out, "  {}:", size)?;
+                    for alternative in &other.alternatives {
+                        for line in alternative.tree(semantics).lines() {
+                            writeln!(out, "    {}", line)?;
+                        }
+                    }
+                }
+                _ => writeln!(
+                    out,
+                    "  {}: {:?}",
+                    size,
+                    other
+                        .alternatives
+                        .iter()
+                        .map(render)
+                        .collect::<Vec<String>>()
+                )?,
+            }
+        }
     }
+    Ok(())
 }
 
-impl NumNode {
-    fn new(value: Rc<RefCell<i32>>) -> NumNode {
-        NumNode { value }
+fn print_template(
+    out: &mut dyn Write,
+    solutions: &Solutions,
+    printed: impl Iterator<Item = i64>,
+    template: &Template,
+    syntax: Syntax,
+    semantics: Semantics,
+    annotations: &Annotations,
+) -> std::io::Result<()> {
+    for v in printed {
+        writeln!(
+            out,
+            "{}",
+            template.render(v, solutions.get(v), syntax, semantics, annotations)
+        )?;
     }
+    Ok(())
 }
 
-struct BinaryNode {
-    left: Rc<dyn Node>,
-    right: Rc<dyn Node>,
-    operation: Rc<RefCell<Operation>>,
+// The alternatives equivalent up to commutativity and associativity, as the first of each
+// class with the number of alternatives in it, in the order of the alternatives.
+fn group(alternatives: &[Expression]) -> Vec<(&Expression, usize)> {
+    let mut classes: Vec<(&Expression, usize)> = vec![];
+    let mut index: HashMap<Expression, usize> = HashMap::new();
+    for alternative in alternatives {
+        match index.entry(alternative.canonical()) {
+            Entry::Occupied(class) => classes[*class.get()].1 += 1,
+            Entry::Vacant(class) => {
+                class.insert(classes.len());
+                classes.push((alternative, 1));
+            }
+        }
+    }
+    classes
 }
 
-impl Display for BinaryNode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(")?;
-        self.left.as_ref().fmt(f)?;
-        write!(f, "{}", self.operation.as_ref().borrow())?;
-        self.right.as_ref().fmt(f)?;
-        write!(f, ")")
+// Tells the number of equivalent alternatives printed as one, if more than one.
+fn suffix(variants: usize) -> String {
+    if variants > 1 {
+        format!(" (×{} variants)", variants)
+    } else {
+        String::new()
     }
 }
 
-impl Node for BinaryNode {
-    fn eval(&self) -> Option<i32> {
-        match *self.operation.as_ref().borrow() {
-            Operation::ADD => self
-                .left
-                .eval()
-                .and_then(|l| self.right.eval().map(|r| l + r)),
-            Operation::SUB => self
-                .left
-                .eval()
-                .and_then(|l| self.right.eval().map(|r| l - r)),
-            Operation::MULT => self
-                .left
-                .eval()
-                .and_then(|l| self.right.eval().map(|r| l * r)),
-            Operation::DIV => self
-                .left
-                .eval()
-                .and_then(|l| self.right.eval().filter(|r| *r != 0).map(|r| l / r)),
+// What is printed next to the solutions, when requested.
+#[derive(Default)]
+struct Annotations {
+    proof: Option<MinimalSizes>,
+    machines: bool,
+    difficulty: bool,
+    // The minimal sizes of the values, when the cheapest solutions can combine more numbers
+    // than needed.
+    sizes: Option<MinimalSizes>,
+    // Alternatives equivalent up to commutativity and associativity are printed once.
+    group: bool,
+    // Expressions are written with the symbols of the operations in mathematics.
+    pretty: bool,
+}
+
+impl Annotations {
+    fn render(&self, syntax: Syntax, expression: &Expression, semantics: Semantics) -> String {
+        syntax.render_as(expression, semantics, self.pretty)
+    }
+}
+
+// The fewest numbers combining into the value, when its difficulty is requested, which is the
+// size of its solution unless the cheapest solutions can combine more numbers than needed.
+fn difficulty(annotations: &Annotations, value: i64, solution: Option<&Solution>) -> Option<usize> {
+    if !annotations.difficulty {
+        return None;
+    }
+    let solution = solution?;
+    match &annotations.sizes {
+        Some(sizes) => sizes.get(value),
+        None => Some(solution.size),
+    }
+}
+
+// The machines of each operation and the extractors of each number needed to build an
+// alternative, e.g. `machines +:1 *:1, extractors 7:1 9:1 10:1`.
+fn machines(expression: &Expression) -> String {
+    let (machines, extractors) = machine_lists(expression);
+    if machines.is_empty() {
+        return format!("machines none, extractors {}", extractors);
+    }
+    format!("machines {}, extractors {}", machines, extractors)
+}
+
+// The machines and the extractors of an alternative as lists of `<operation>:<count>` and
+// `<number>:<count>` separated by spaces.
+fn machine_lists(expression: &Expression) -> (String, String) {
+    let machines: Vec<String> = expression
+        .machines()
+        .iter()
+        .map(|(operation, count)| format!("{}:{}", operation, count))
+        .collect();
+    let extractors: Vec<String> = expression
+        .extractors()
+        .iter()
+        .map(|(number, count)| format!("{}:{}", number, count))
+        .collect();
+    (machines.join(" "), extractors.join(" "))
+}
+
+#[derive(Clone, Serialize)]
+struct JsonMachines {
+    machines: BTreeMap<String, usize>,
+    extractors: BTreeMap<i64, usize>,
+}
+
+// The machines of every alternative of a solution, none for unsolved values.
+fn json_machines(solution: Option<&Solution>) -> Vec<JsonMachines> {
+    solution
+        .map(|solution| {
+            solution
+                .alternatives
+                .iter()
+                .map(JsonMachines::new)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl JsonMachines {
+    fn new(expression: &Expression) -> JsonMachines {
+        JsonMachines {
+            machines: expression.machines().into_iter().collect(),
+            extractors: expression.extractors().into_iter().collect(),
         }
     }
 }
 
-impl BinaryNode {
-    fn new(l: Rc<dyn Node>, r: Rc<dyn Node>, o: Rc<RefCell<Operation>>) -> BinaryNode {
-        BinaryNode {
-            left: l,
-            right: r,
-            operation: o,
+#[derive(Serialize)]
+struct JsonMetadata {
+    max_number: Option<i64>,
+    numbers: Vec<i64>,
+    max_size: usize,
+    operations: Vec<String>,
+    target: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    targets: Option<Vec<i64>>,
+    overflows: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    truncated_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    truncated_by: Option<&'static str>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    degraded: bool,
+}
+
+// Whether the size of the solution was proved minimal, when requested.
+fn minimal(
+    proof: Option<&MinimalSizes>,
+    value: i64,
+    solution: &Solution,
+    semantics: Semantics,
+) -> Option<bool> {
+    proof.map(|proof| proof.proves(value, solution, semantics))
+}
+
+#[derive(Clone, Serialize)]
+struct JsonValue {
+    value: i64,
+    size: Option<usize>,
+    cost: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    minimal: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    difficulty: Option<usize>,
+    count: u64,
+    alternatives: Vec<String>,
+    // The machines and extractors of every alternative, when requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    machines: Option<Vec<JsonMachines>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    other_sizes: Vec<JsonOtherSize>,
+}
+
+// The solution of a value at another size, with `--all-sizes`.
+#[derive(Clone, Serialize)]
+struct JsonOtherSize {
+    size: usize,
+    cost: u64,
+    count: u64,
+    alternatives: Vec<String>,
+}
+
+impl JsonValue {
+    fn new(
+        value: i64,
+        solution: Option<&Solution>,
+        syntax: Syntax,
+        semantics: Semantics,
+    ) -> JsonValue {
+        match solution {
+            Some(solution) => JsonValue {
+                value,
+                size: Some(solution.size),
+                cost: Some(solution.cost),
+                minimal: None,
+                difficulty: None,
+                count: solution.count,
+                alternatives: solution
+                    .alternatives
+                    .iter()
+                    .map(|alternative| syntax.render(alternative, semantics))
+                    .collect(),
+                machines: None,
+                other_sizes: solution
+                    .other_sizes
+                    .iter()
+                    .map(|other| JsonOtherSize {
+                        size: other.size,
+                        cost: other.cost,
+                        count: other.count,
+                        alternatives: other
+                            .alternatives
+                            .iter()
+                            .map(|alternative| syntax.render(alternative, semantics))
+                            .collect(),
+                    })
+                    .collect(),
+            },
+            None => JsonValue {
+                value,
+                size: None,
+                cost: None,
+                minimal: None,
+                difficulty: None,
+                count: 0,
+                alternatives: vec![],
+                machines: None,
+                other_sizes: vec![],
+            },
         }
     }
 }
 
-struct Composed {
-    ints: Vec<Rc<RefCell<i32>>>,
-    ops: Vec<Rc<RefCell<Operation>>>,
-    alternatives: Vec<Rc<dyn Node>>,
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    #[serde(flatten)]
+    metadata: &'a JsonMetadata,
+    values: Vec<JsonValue>,
 }
 
-fn make_options(size: usize) -> Composed {
-    let mut ints: Vec<Rc<RefCell<i32>>> = Vec::with_capacity(size);
-    for _ in 0..size {
-        ints.push(Rc::new(RefCell::new(0)));
+fn print_json(
+    out: &mut dyn Write,
+    solutions: &Solutions,
+    printed: impl Iterator<Item = i64>,
+    metadata: &JsonMetadata,
+    syntax: Syntax,
+    semantics: Semantics,
+    annotations: &Annotations,
+) -> std::io::Result<()> {
+    let values = printed
+        .map(|v| {
+            let solution = solutions.get(v);
+            let mut entry = JsonValue::new(v, solution, syntax, semantics);
+            entry.minimal = solution
+                .and_then(|solution| minimal(annotations.proof.as_ref(), v, solution, semantics));
+            entry.difficulty = difficulty(annotations, v, solution);
+            if annotations.machines {
+                entry.machines = Some(json_machines(solution));
+            }
+            entry
+        })
+        .collect();
+    serde_json::to_writer(&mut *out, &JsonReport { metadata, values })?;
+    writeln!(out)
+}
+
+fn print_delimited(
+    out: &mut dyn Write,
+    solutions: &Solutions,
+    printed: impl Iterator<Item = i64>,
+    separator: char,
+    syntax: Syntax,
+    semantics: Semantics,
+    annotations: &Annotations,
+) -> std::io::Result<()> {
+    let proof = annotations.proof.as_ref();
+    // The difficulty, the machines of the expression and the minimality are printed in the last
+    // columns, when requested.
+    let mut columns = String::new();
+    if annotations.difficulty {
+        columns.push_str(&format!("{}difficulty", separator));
     }
-    let mut num_nodes: Vec<Rc<dyn Node>> = Vec::with_capacity(size);
-    for i in 0..size {
-        num_nodes.push(Rc::new(NumNode::new(ints[i].clone())));
+    if annotations.machines {
+        columns.push_str(&format!("{0}machines{0}extractors", separator));
     }
-    let mut operations: Vec<Rc<RefCell<Operation>>> = Vec::with_capacity(size - 1);
-    for _ in 0..(size - 1) {
-        operations.push(Rc::new(RefCell::new(Operation::ADD)));
+    if proof.is_some() {
+        columns.push_str(&format!("{}minimal", separator));
     }
-    let alternatives = calculate_parenthesisations(0, size, &num_nodes, &operations);
-    Composed {
-        ints,
-        ops: operations,
-        alternatives,
+    writeln!(
+        out,
+        "value{0}size{0}cost{0}alternatives{0}expression{1}",
+        separator, columns
+    )?;
+    for v in printed {
+        let mut columns = String::new();
+        let solution = solutions.get(v);
+        if annotations.difficulty {
+            columns.push(separator);
+            if let Some(difficulty) = difficulty(annotations, v, solution) {
+                columns.push_str(&difficulty.to_string());
+            }
+        }
+        if annotations.machines {
+            let (machines, extractors) = solution
+                .and_then(|solution| solution.alternatives.first())
+                .map(machine_lists)
+                .unwrap_or_default();
+            columns.push_str(&format!("{0}{1}{0}{2}", separator, machines, extractors));
+        }
+        if proof.is_some() {
+            columns.push(separator);
+            if let Some(minimal) =
+                solution.and_then(|solution| minimal(proof, v, solution, semantics))
+            {
+                columns.push_str(&minimal.to_string());
+            }
+        }
+        match solution {
+            Some(solution) => writeln!(
+                out,
+                "{1}{0}{2}{0}{3}{0}{4}{0}{5}{6}",
+                separator,
+                v,
+                solution.size,
+                solution.cost,
+                solution.count,
+                solution
+                    .alternatives
+                    .first()
+                    .map(|alternative| syntax.render(alternative, semantics))
+                    .unwrap_or_default(),
+                columns
+            )?,
+            None => writeln!(out, "{1}{0}{0}{0}0{0}{2}", separator, v, columns)?,
+        }
     }
+    Ok(())
 }
 
-fn calculate_parenthesisations(
-    left: usize,
-    right: usize,
-    nodes: &Vec<Rc<dyn Node>>,
-    operations: &Vec<Rc<RefCell<Operation>>>,
-) -> Vec<Rc<dyn Node>> {
-    if left + 1 == right {
-        return vec![nodes[left].clone()];
+// Missing values as inclusive ranges, each of a single value unless `grouped`.
+fn missing(
+    solutions: &Solutions,
+    printed: impl Iterator<Item = i64>,
+    grouped: bool,
+) -> Vec<(i64, i64)> {
+    let mut ranges: Vec<(i64, i64)> = vec![];
+    for v in printed.filter(|v| solutions.get(*v).is_none()) {
+        match ranges.last_mut() {
+            Some((_, to)) if grouped && *to + 1 == v => *to = v,
+            _ => ranges.push((v, v)),
+        }
     }
+    ranges
+}
 
-    if left + 2 == right {
-        return vec![Rc::new(BinaryNode::new(
-            nodes[left].clone(),
-            nodes[left + 1].clone(),
-            operations[left].clone(),
-        ))];
+#[derive(Serialize)]
+#[serde(untagged)]
+enum JsonMissing {
+    Value(i64),
+    Range { from: i64, to: i64 },
+}
+
+#[derive(Serialize)]
+struct JsonMissingReport<'a> {
+    #[serde(flatten)]
+    metadata: &'a JsonMetadata,
+    missing: Vec<JsonMissing>,
+}
+
+fn print_missing(
+    out: &mut dyn Write,
+    missing: &[(i64, i64)],
+    format: Format,
+    metadata: &JsonMetadata,
+) -> std::io::Result<()> {
+    match format {
+        Format::Text | Format::Dot | Format::Latex => {
+            for (from, to) in missing {
+                if from == to {
+                    writeln!(out, "{}", from)?;
+                } else {
+                    writeln!(out, "{}-{}", from, to)?;
+                }
+            }
+            Ok(())
+        }
+        Format::Csv | Format::Tsv => {
+            let separator = if matches!(format, Format::Csv) {
+                ','
+            } else {
+                '\t'
+            };
+            writeln!(out, "from{}to", separator)?;
+            for (from, to) in missing {
+                writeln!(out, "{}{}{}", from, separator, to)?;
+            }
+            Ok(())
+        }
+        Format::Json => {
+            let missing = missing
+                .iter()
+                .map(|(from, to)| {
+                    if from == to {
+                        JsonMissing::Value(*from)
+                    } else {
+                        JsonMissing::Range {
+                            from: *from,
+                            to: *to,
+                        }
+                    }
+                })
+                .collect();
+            serde_json::to_writer(&mut *out, &JsonMissingReport { metadata, missing })?;
+            writeln!(out)
+        }
     }
-    let mut result: Vec<Rc<dyn Node>> = Vec::new();
-    for i in (left + 1)..right {
-        let left_combinations = calculate_parenthesisations(left, i, nodes, operations);
-        let right_combinations = calculate_parenthesisations(i, right, nodes, operations);
-        for left_node in left_combinations.iter() {
-            for right_node in right_combinations.iter() {
-                result.push(Rc::new(BinaryNode::new(
-                    left_node.clone(),
-                    right_node.clone(),
-                    operations[i - 1].clone(),
-                )))
+}
+
+#[derive(Serialize)]
+struct JsonRichness {
+    value: i64,
+    size: Option<usize>,
+    cost: Option<u64>,
+    distinct: u64,
+}
+
+#[derive(Serialize)]
+struct JsonRichnessReport<'a> {
+    #[serde(flatten)]
+    metadata: &'a JsonMetadata,
+    values: Vec<JsonRichness>,
+}
+
+// Prints the number of alternatives distinct up to commutativity and associativity of every
+// value, the solutions being searched without keeping their alternatives.
+fn print_richness(
+    out: &mut dyn Write,
+    solutions: &Solutions,
+    printed: impl Iterator<Item = i64>,
+    format: Format,
+    show_cost: bool,
+    metadata: &JsonMetadata,
+) -> std::io::Result<()> {
+    match format {
+        Format::Text | Format::Dot | Format::Latex => {
+            for v in printed {
+                match solutions.get(v) {
+                    Some(solution) if show_cost => writeln!(
+                        out,
+                        "{} -> ({}, cost {}) {} distinct",
+                        v, solution.size, solution.cost, solution.count
+                    )?,
+                    Some(solution) => writeln!(
+                        out,
+                        "{} -> ({}) {} distinct",
+                        v, solution.size, solution.count
+                    )?,
+                    None => writeln!(out, "{} -> None", v)?,
+                }
+            }
+            Ok(())
+        }
+        Format::Csv | Format::Tsv => {
+            let separator = if matches!(format, Format::Csv) {
+                ','
+            } else {
+                '\t'
+            };
+            writeln!(out, "value{0}size{0}cost{0}distinct", separator)?;
+            for v in printed {
+                match solutions.get(v) {
+                    Some(solution) => writeln!(
+                        out,
+                        "{1}{0}{2}{0}{3}{0}{4}",
+                        separator, v, solution.size, solution.cost, solution.count
+                    )?,
+                    None => writeln!(out, "{1}{0}{0}{0}0", separator, v)?,
+                }
             }
+            Ok(())
+        }
+        Format::Json => {
+            let values = printed
+                .map(|value| match solutions.get(value) {
+                    Some(solution) => JsonRichness {
+                        value,
+                        size: Some(solution.size),
+                        cost: Some(solution.cost),
+                        distinct: solution.count,
+                    },
+                    None => JsonRichness {
+                        value,
+                        size: None,
+                        cost: None,
+                        distinct: 0,
+                    },
+                })
+                .collect();
+            serde_json::to_writer(&mut *out, &JsonRichnessReport { metadata, values })?;
+            writeln!(out)
         }
     }
-    return result;
 }
 
-struct OperationDictionary {
-    operations: Vec<Operation>,
-    indexes: HashMap<Operation, usize>,
+// The results are written next to `path` and renamed over it once complete, so that an
+// interrupted run never leaves a partial report behind.
+fn write_atomically(
+    path: &Path,
+    print: impl FnOnce(&mut dyn Write) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let result = File::create(&temporary).and_then(|file| {
+        let mut out = BufWriter::new(file);
+        print(&mut out)?;
+        out.into_inner()?.sync_all()?;
+        fs::rename(&temporary, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
 }
 
-impl OperationDictionary {
-    fn new(options: &Vec<String>) -> Option<OperationDictionary> {
-        let mut operations: Vec<Operation> = vec![];
-        let mut indexes: HashMap<Operation, usize> = HashMap::new();
-        let mut error = false;
-        for option in options.iter() {
-            match option.as_str() {
-                "+" => {
-                    operations.push(Operation::ADD);
-                    indexes.insert(Operation::ADD, operations.len() - 1);
+// One cluster per solved value, drawing the tree of its first alternative.
+fn print_dot(
+    out: &mut dyn Write,
+    solutions: &Solutions,
+    printed: impl Iterator<Item = i64>,
+    semantics: Semantics,
+) -> std::io::Result<()> {
+    let mut graph = String::from("digraph solutions {\n");
+    for v in printed {
+        if let Some(expression) = solutions
+            .get(v)
+            .and_then(|solution| solution.alternatives.first())
+        {
+            graph.push_str(&format!("subgraph cluster_{0} {{\n  label=\"{0}\";\n", v));
+            expression
+                .write_dot(&mut graph, &format!("v{}_", v), semantics)
+                .expect("writing to a String cannot fail");
+            graph.push_str("}\n");
+        }
+    }
+    graph.push_str("}\n");
+    out.write_all(graph.as_bytes())
+}
+
+// An `align*` environment with a row per alternative, the value on the first of its own, and
+// the unsolved values as comments.
+fn print_latex(
+    out: &mut dyn Write,
+    solutions: &Solutions,
+    printed: impl Iterator<Item = i64>,
+    annotations: &Annotations,
+) -> std::io::Result<()> {
+    let mut rows = vec![];
+    let mut unsolved = vec![];
+    for v in printed {
+        match solutions.get(v) {
+            Some(solution) => {
+                let alternatives: Vec<&Expression> = if annotations.group {
+                    group(&solution.alternatives)
+                        .into_iter()
+                        .map(|(expression, _)| expression)
+                        .collect()
+                } else {
+                    solution.alternatives.iter().collect()
+                };
+                for (i, expression) in alternatives.into_iter().enumerate() {
+                    let value = if i == 0 { v.to_string() } else { String::new() };
+                    rows.push(format!("{} &= {}", value, expression.latex()));
                 }
-                "-" => {
-                    operations.push(Operation::SUB);
-                    indexes.insert(Operation::SUB, operations.len() - 1);
+            }
+            None => unsolved.push(v),
+        }
+    }
+    if !rows.is_empty() {
+        writeln!(out, "\\begin{{align*}}")?;
+        writeln!(out, "{}", rows.join(" \\\\\n"))?;
+        writeln!(out, "\\end{{align*}}")?;
+    }
+    for v in unsolved {
+        writeln!(out, "% {}: no solution", v)?;
+    }
+    Ok(())
+}
+
+// Parses a CSV list of `<operation>:<value>` into the values of the binary and of the unary
+// operations.
+fn parse_per_operation<T: FromStr>(
+    list: &str,
+) -> Option<(HashMap<Operation, T>, HashMap<UnaryOperation, T>)> {
+    let mut values = HashMap::new();
+    let mut unary_values = HashMap::new();
+    for entry in list.split(",") {
+        let (symbol, value) = entry.rsplit_once(":")?;
+        let value = value.trim().parse().ok()?;
+        match (
+            Operation::parse(symbol.trim()),
+            UnaryOperation::parse(symbol.trim()),
+        ) {
+            (Some(operation), _) => values.insert(operation, value),
+            (None, Some(operation)) => unary_values.insert(operation, value),
+            (None, None) => return None,
+        };
+    }
+    Some((values, unary_values))
+}
+
+// The costs of a cost model file, in TOML or, with a `.json` extension, in JSON, e.g.
+//
+// [operations]
+// "/" = 3
+// [numbers]
+// 11 = 5
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CostModel {
+    #[serde(default)]
+    operations: HashMap<String, u64>,
+    #[serde(default)]
+    numbers: HashMap<String, u64>,
+}
+
+fn cost_model(path: &Path) -> CostModel {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            fail(
+                Failure::Io,
+                format!("unable to read cost model {}: {}", path.display(), e),
+            );
+        }
+    };
+    let model = if path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        serde_json::from_str(&content).map_err(|e| e.to_string())
+    } else {
+        toml::from_str(&content).map_err(|e| e.to_string())
+    };
+    match model {
+        Ok(model) => model,
+        Err(e) => {
+            fail(
+                Failure::Arguments,
+                format!("invalid cost model {}: {}", path.display(), e),
+            );
+        }
+    }
+}
+
+// The costs of the cost model, overridden by the operation costs given with `--op-cost`.
+fn objective(op_cost: &Option<String>, cost_model_path: &Option<PathBuf>) -> Objective {
+    let model = cost_model_path
+        .as_ref()
+        .map(|path| cost_model(path))
+        .unwrap_or_default();
+    let mut costs = HashMap::new();
+    let mut unary_costs = HashMap::new();
+    for (symbol, cost) in model.operations {
+        match (Operation::parse(&symbol), UnaryOperation::parse(&symbol)) {
+            (Some(operation), _) => {
+                costs.insert(operation, cost);
+            }
+            (None, Some(operation)) => {
+                unary_costs.insert(operation, cost);
+            }
+            (None, None) => {
+                fail(
+                    Failure::Arguments,
+                    format!("unrecognised operation {} in the cost model", symbol),
+                );
+            }
+        }
+    }
+    let number_costs: Option<HashMap<i64, u64>> = model
+        .numbers
+        .into_iter()
+        .map(|(number, cost)| Some((number.trim().parse().ok()?, cost)))
+        .collect();
+    let Some(number_costs) = number_costs else {
+        fail(
+            Failure::Arguments,
+            "the numbers of the cost model must be integers",
+        );
+    };
+    if let Some(op_costs) = op_cost {
+        match parse_per_operation(op_costs) {
+            Some((op_costs, unary_op_costs)) => {
+                costs.extend(op_costs);
+                unary_costs.extend(unary_op_costs);
+            }
+            None => {
+                fail(
+                    Failure::Arguments,
+                    format!(
+                        "op_cost must be a CSV list of <operation>:<cost>, was {}",
+                        op_costs
+                    ),
+                );
+            }
+        }
+    }
+    Objective::weighted(costs, unary_costs).with_number_costs(number_costs)
+}
+
+fn limits(
+    max_uses: &Option<String>,
+    source_limit: &Option<String>,
+    must_use: &Option<String>,
+    max_depth: Option<usize>,
+) -> Limits {
+    let (uses, unary_uses) = match max_uses {
+        Some(max_uses_list) => match parse_per_operation(max_uses_list) {
+            Some(limits) => limits,
+            None => {
+                fail(
+                    Failure::Arguments,
+                    format!(
+                        "max_uses must be a CSV list of <operation>:<count>, was {}",
+                        max_uses_list
+                    ),
+                );
+            }
+        },
+        None => (HashMap::new(), HashMap::new()),
+    };
+    let sources = match source_limit {
+        Some(source_limit_list) => {
+            let sources: Option<HashMap<i64, usize>> = source_limit_list
+                .split(",")
+                .map(|entry| {
+                    let (number, count) = entry.split_once(":")?;
+                    Some((number.trim().parse().ok()?, count.trim().parse().ok()?))
+                })
+                .collect();
+            match sources {
+                Some(sources) => sources,
+                None => {
+                    fail(
+                        Failure::Arguments,
+                        format!(
+                            "source_limit must be a CSV list of <number>:<count>, was {}",
+                            source_limit_list
+                        ),
+                    );
                 }
-                "*" => {
-                    operations.push(Operation::MULT);
-                    indexes.insert(Operation::MULT, operations.len() - 1);
+            }
+        }
+        None => HashMap::new(),
+    };
+    let required: Vec<i64> = match must_use {
+        Some(must_use_list) => {
+            let required: Result<Vec<i64>, _> =
+                must_use_list.split(",").map(|s| s.trim().parse()).collect();
+            match required {
+                Ok(mut required) => {
+                    required.sort();
+                    required.dedup();
+                    required
                 }
-                "/" => {
-                    operations.push(Operation::DIV);
-                    indexes.insert(Operation::DIV, operations.len() - 1);
+                Err(_) => {
+                    fail(
+                        Failure::Arguments,
+                        format!(
+                            "must_use must be a CSV list of integers, was {}",
+                            must_use_list
+                        ),
+                    );
+                }
+            }
+        }
+        None => vec![],
+    };
+    Limits::new(uses, unary_uses, sources)
+        .require(required)
+        .limit_depth(max_depth)
+}
+
+fn forbidden_adjacent(forbid_adjacent: &Option<String>) -> Vec<(Operation, Operation)> {
+    match forbid_adjacent {
+        Some(forbid_adjacent_list) => {
+            let pairs: Option<Vec<(Operation, Operation)>> = forbid_adjacent_list
+                .split(";")
+                .map(|pair| {
+                    let (producer, consumer) = pair.split_once(",")?;
+                    Some((
+                        Operation::parse(producer.trim())?,
+                        Operation::parse(consumer.trim())?,
+                    ))
+                })
+                .collect();
+            match pairs {
+                Some(pairs) => pairs,
+                None => {
+                    fail(
+                        Failure::Arguments,
+                        format!(
+                            "forbid_adjacent must be a list of <operation>,<operation> pairs separated by ;, was {}",
+                            forbid_adjacent_list
+                        ),
+                    );
+                }
+            }
+        }
+        None => vec![],
+    }
+}
+
+fn preference(prefer: &Option<String>) -> Vec<Operation> {
+    match prefer {
+        Some(prefer_list) => {
+            let preference: Option<Vec<Operation>> = prefer_list
+                .split(",")
+                .map(|symbol| Operation::parse(symbol.trim()))
+                .collect();
+            match preference {
+                Some(preference) => preference,
+                None => {
+                    fail(
+                        Failure::Arguments,
+                        format!(
+                            "prefer must be a CSV list of binary operations, was {}",
+                            prefer_list
+                        ),
+                    );
+                }
+            }
+        }
+        None => vec![],
+    }
+}
+
+// One target per line, ignoring empty lines and comments starting with `#`.
+fn read_targets(path: &Path) -> Vec<i64> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            fail(
+                Failure::Io,
+                format!("unable to read targets from {}: {}", path.display(), e),
+            );
+        }
+    };
+    let mut targets = vec![];
+    for (i, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        match line.parse() {
+            Ok(target) => targets.push(target),
+            Err(_) => {
+                fail(
+                    Failure::Arguments,
+                    format!(
+                        "targets must be integers, was {} at {}:{}",
+                        line,
+                        path.display(),
+                        i + 1
+                    ),
+                );
+            }
+        }
+    }
+    targets
+}
+
+// The largest size searched by `--until-covered` without `--max-size`, beyond the sizes that can
+// be searched in practice.
+const MAX_COVERING_SIZE: usize = 16;
+
+// Builds the configuration shared by all the commands, exiting on invalid arguments.
+fn solver_config(args: &SearchArgs) -> SolverConfig {
+    build(solver_builder(args))
+}
+
+// The configuration shared by all the commands, for them to complete before building it.
+fn solver_builder(args: &SearchArgs) -> SolverConfigBuilder {
+    let numbers = match (&args.numbers, args.max_number) {
+        (Some(numbers_arg), _) => {
+            let numbers: Result<Vec<i64>, _> =
+                numbers_arg.split(",").map(|s| s.trim().parse()).collect();
+            match numbers {
+                Ok(mut numbers) if numbers.iter().all(|n| *n > 0) => {
+                    numbers.sort();
+                    numbers.dedup();
+                    numbers
                 }
                 _ => {
-                    error = true;
+                    fail(
+                        Failure::Arguments,
+                        format!(
+                            "numbers must be a CSV list of integers > 0, was {}",
+                            numbers_arg
+                        ),
+                    );
                 }
             }
         }
-        if error {
-            None
-        } else {
-            Some(OperationDictionary {
-                operations,
-                indexes,
-            })
+        (None, Some(max_number)) if max_number > 0 => (1..(max_number + 1)).collect(),
+        (None, max_number) => {
+            fail(
+                Failure::Arguments,
+                format!("max_number must be > 0, was {}", max_number.unwrap_or(0)),
+            );
+        }
+    };
+    // Values already produced are combined like the numbers of the extractors.
+    let numbers = match &args.have {
+        Some(have_arg) => {
+            let have: Result<Vec<i64>, _> = have_arg.split(",").map(|s| s.trim().parse()).collect();
+            match have {
+                Ok(have) if have.iter().all(|n| *n > 0) => {
+                    let mut numbers = numbers;
+                    numbers.extend(have);
+                    numbers.sort();
+                    numbers.dedup();
+                    numbers
+                }
+                _ => {
+                    fail(
+                        Failure::Arguments,
+                        format!("have must be a CSV list of integers > 0, was {}", have_arg),
+                    );
+                }
+            }
+        }
+        None => numbers,
+    };
+    // Excluded numbers are never combined, even within `max_number`.
+    let numbers = match &args.exclude {
+        Some(exclude_arg) => {
+            let exclude: Result<Vec<i64>, _> =
+                exclude_arg.split(",").map(|s| s.trim().parse()).collect();
+            match exclude {
+                Ok(exclude) => {
+                    let numbers: Vec<i64> = numbers
+                        .into_iter()
+                        .filter(|n| !exclude.contains(n))
+                        .collect();
+                    if numbers.is_empty() {
+                        fail(Failure::Arguments, "exclude leaves no numbers to combine");
+                    }
+                    numbers
+                }
+                Err(_) => {
+                    fail(
+                        Failure::Arguments,
+                        format!(
+                            "exclude must be a CSV list of integers, was {}",
+                            exclude_arg
+                        ),
+                    );
+                }
+            }
         }
+        None => numbers,
+    };
+    let operations_arg = args.operations.clone().unwrap_or("+,-,*,/".to_string());
+    let operations: Vec<&str> = operations_arg.split(",").collect();
+    let limits = limits(
+        &args.max_uses,
+        &args.source_limit,
+        &args.must_use,
+        args.max_depth,
+    )
+    .forbid_adjacent(forbidden_adjacent(&args.forbid_adjacent))
+    .restrict_to_chains(args.chain_only);
+    if args.max_size.is_none() && matches!(args.solver, SolverKind::MeetInTheMiddle) {
+        fail(
+            Failure::Arguments,
+            "the meet-in-the-middle solver needs max_size, even with until_covered",
+        );
+    }
+    if args.all_sizes && !matches!(args.solver, SolverKind::BruteForce) {
+        fail(
+            Failure::Arguments,
+            "all_sizes is only supported by the brute-force solver",
+        );
     }
+    // Without targets, the sizes are searched until every value up to the largest number, or
+    // the fraction of them to cover, is solved.
+    let cover = (args.until_covered || args.coverage.is_some())
+        .then(|| 1..=numbers.iter().copied().max().unwrap_or(1));
+    SolverConfig::builder()
+        .numbers(numbers)
+        .max_size(args.max_size.unwrap_or(MAX_COVERING_SIZE))
+        .operations(&operations)
+        .strategy(match args.solver {
+            SolverKind::BruteForce => Strategy::BruteForce,
+            SolverKind::Dp => Strategy::Dynamic,
+            SolverKind::Heuristic => Strategy::Heuristic,
+            SolverKind::Ida => Strategy::Ida,
+            SolverKind::MeetInTheMiddle => Strategy::MeetInTheMiddle,
+        })
+        .semantics(semantics(
+            args.width,
+            args.allow_truncating_division,
+            args.max_intermediate,
+            args.no_negative_intermediates,
+        ))
+        .objective(objective(&args.op_cost, &args.cost_model))
+        .limits(limits)
+        .max_alternatives(args.top_k)
+        .all_sizes(args.all_sizes)
+        .cover(cover)
+        .coverage(args.coverage)
+        .dedupe(args.dedupe)
+        .preference(preference(&args.prefer))
+        .prefer_few_sources(args.prefer_few_sources)
+        .prefer_shallow(args.prefer_shallow)
+        .timeout(args.timeout.map(Duration::from_secs))
+        .max_memory(args.max_memory.map(|megabytes| megabytes << 20))
+}
 
-    fn operation(&self, index: usize) -> Operation {
-        self.operations[index]
+// Exits on invalid settings.
+fn build(builder: SolverConfigBuilder) -> SolverConfig {
+    match builder.build() {
+        Ok(config) => config,
+        Err(e) => fail(Failure::Arguments, e.to_string()),
     }
+}
 
-    fn index(&self, operation: &Operation) -> usize {
-        *self.indexes.get(operation).unwrap()
+fn configure_threads(threads: Option<usize>) {
+    if let Some(threads) = threads {
+        if let Err(e) = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+        {
+            fail(
+                Failure::Arguments,
+                format!("unable to configure {} threads: {}", threads, e),
+            );
+        }
     }
+}
 
-    fn max_operation(&self) -> Operation {
-        self.operations[self.operations.len() - 1]
+// The progress bars are left out when the events of every size are logged.
+fn progress(args: &SearchArgs) -> Box<dyn Progress> {
+    if args.no_progress || tracing::enabled!(Level::DEBUG) {
+        Box::new(Logged::new(Box::new(NoProgress)))
+    } else {
+        Box::new(Logged::new(Box::new(ProgressBars::new())))
     }
 }
 
-fn main() {
-    let args = Args::parse();
-    if args.max_number <= 0 {
-        println!("max_number must be > 0, was {}", args.max_number);
-        exit(1);
-    }
-    let operations_arg = args.operations.unwrap_or("+,-,*,/".to_string());
-    let operations = operations_arg.split(",").map(|s| s.to_string()).collect();
-    let operation_dictionary = OperationDictionary::new(&operations);
-    if operation_dictionary.is_none() {
-        println!(
-            "unrecognised operations found, allowed=[+,-,*,/], provided={:?}",
-            operations
+// The checkpoint cached in `directory` for the settings of `config`, if any, and where to save
+// it. Searches are resumed from their cached checkpoint to compute only the larger sizes, while
+// a checkpoint of larger sizes than requested is left untouched and the search starts over.
+fn cached(directory: &Path, config: &SolverConfig) -> (Option<Checkpoint>, Option<PathBuf>) {
+    if let Err(e) = fs::create_dir_all(directory) {
+        fail(
+            Failure::Io,
+            format!("unable to create cache {}: {}", directory.display(), e),
         );
-        exit(1);
-    }
-    let operation_dictionary = operation_dictionary.unwrap();
-    let maximum_number = args.max_number;
-    let maximum_size = args.max_size;
-    let mut dictionary: HashMap<i32, (usize, Vec<String>)> = HashMap::new();
-    let mut maximum_composed = 1;
-    for size in 1..(maximum_size + 1) {
-        let composed = make_options(size);
-        let mut op_finished = false;
-        while !op_finished {
-            for i in 0..composed.ints.len() {
-                composed.ints[i].replace(1);
-            }
-            let limit = maximum_number;
-            let mut finished = false;
-            while !finished {
-                for alternative in &composed.alternatives {
-                    if let Some(v) = alternative.eval() {
-                        if v > maximum_composed {
-                            maximum_composed = v;
-                        }
-                        if !dictionary.contains_key(&v) {
-                            dictionary.insert(v, (size, vec![format!("{}", alternative)]));
-                        } else {
-                            let (max_size, options) = dictionary.get_mut(&v).unwrap();
-                            if *max_size == size {
-                                options.push(format!("{}", alternative));
-                            }
-                        }
+    }
+    let path = directory.join(format!("{}.json", Checkpoint::key(config)));
+    if !path.exists() {
+        return (None, Some(path));
+    }
+    match Checkpoint::load(&path) {
+        Ok(checkpoint) if checkpoint.size() > config.max_size() + 1 => (None, None),
+        Ok(checkpoint) => (Some(checkpoint), Some(path)),
+        Err(e) => {
+            warn!("ignoring cache {}: {}", path.display(), e);
+            (None, Some(path))
+        }
+    }
+}
+
+// Runs the search, resuming from and saving checkpoints as requested, and exits on invalid
+// arguments.
+fn search(
+    args: &SearchArgs,
+    checkpoints: &CheckpointArgs,
+    targets: Vec<i64>,
+    cover: Option<RangeInclusive<i64>>,
+    count_distinct: bool,
+    progress: &dyn Progress,
+) -> (Solver, Solutions) {
+    configure_threads(args.threads);
+    if !matches!(args.solver, SolverKind::BruteForce)
+        && (checkpoints.checkpoint.is_some()
+            || checkpoints.resume.is_some()
+            || checkpoints.cache.is_some())
+    {
+        fail(
+            Failure::Arguments,
+            "checkpoint, resume and cache are only supported by the brute-force solver",
+        );
+    }
+    let mut builder = solver_builder(args);
+    // The targets, or the printed range, are what `until_covered` covers.
+    if !targets.is_empty() || cover.is_some() {
+        builder = builder.cover(cover);
+    }
+    let builder = builder.targets(targets).count_distinct(count_distinct);
+    let config = build(builder.clone());
+    let (resumed, path) = match &checkpoints.cache {
+        Some(directory) => cached(directory, &config),
+        None => (
+            checkpoints
+                .resume
+                .as_ref()
+                .map(|path| match Checkpoint::load(path) {
+                    Ok(checkpoint) => checkpoint,
+                    Err(e) => {
+                        fail(
+                            Failure::Io,
+                            format!("unable to load checkpoint {}: {}", path.display(), e),
+                        );
                     }
-                }
-                let mut i: usize = 0;
-                while i < composed.ints.len() && *composed.ints[i].borrow() == limit {
-                    composed.ints[i].replace(1);
-                    i += 1;
-                }
-                if i < composed.ints.len() {
-                    let current = *composed.ints[i].borrow();
-                    composed.ints[i].replace(current + 1);
-                } else {
-                    finished = true;
-                }
+                }),
+            // Resumed searches keep saving to the checkpoint they were loaded from by default.
+            checkpoints
+                .checkpoint
+                .clone()
+                .or(checkpoints.resume.clone()),
+        ),
+    };
+    let solver = Solver::new(build(builder.checkpoint(path.map(|path| {
+        CheckpointPolicy {
+            path,
+            interval: Duration::from_secs(checkpoints.checkpoint_interval),
+        }
+    }))));
+    let solutions = match resumed {
+        Some(checkpoint) => match solver.resume_with_progress(checkpoint, progress) {
+            Ok(solutions) => solutions,
+            Err(e) => {
+                fail(Failure::Arguments, format!("unable to resume: {}", e));
             }
+        },
+        None => solver.solve_with_progress(progress),
+    };
+    if solutions.overflows() > 0 {
+        info!(
+            "{} candidates discarded for overflow",
+            solutions.overflows()
+        );
+    }
+    warn_truncated(&solutions);
+    report_covered(args, solver.config(), &solutions);
+    (solver, solutions)
+}
 
-            let mut op: usize = 0;
-            while op < composed.ops.len()
-                && *composed.ops[op].borrow() == operation_dictionary.max_operation()
-            {
-                composed.ops[op].replace(operation_dictionary.operation(0));
-                op += 1;
+// Tells the size a covered range, or the fraction of it to cover, was solved with, and warns
+// when `until_covered` stopped before covering it.
+fn report_covered(args: &SearchArgs, config: &SolverConfig, solutions: &Solutions) {
+    let Some(cover) = config.cover() else {
+        return;
+    };
+    let size = solutions.size_searched();
+    let total = (cover.end() - cover.start() + 1).max(0) as usize;
+    let solved = cover
+        .clone()
+        .filter(|value| solutions.get(*value).is_some())
+        .count();
+    if solved == total {
+        if size < config.max_size() || args.until_covered {
+            info!(
+                "every value from {} to {} covered, stopped after size {}",
+                cover.start(),
+                cover.end(),
+                size
+            );
+        }
+    } else if config
+        .coverage()
+        .is_some_and(|coverage| solved as f64 >= coverage * total as f64)
+    {
+        info!(
+            "{} of the values from {} to {} covered ({:.1}%), stopped after size {}",
+            solved,
+            cover.start(),
+            cover.end(),
+            100.0 * solved as f64 / total as f64,
+            size
+        );
+    } else if args.until_covered {
+        warn!(
+            "values from {} to {} left uncovered after size {}",
+            cover.start(),
+            cover.end(),
+            size
+        );
+    }
+}
+
+fn warn_truncated(solutions: &Solutions) {
+    if let Some(e) = solutions.unsaved_checkpoint() {
+        warn!("{}", e);
+    }
+    if solutions.degraded() {
+        warn!("memory budget reached, kept a single alternative per value");
+    }
+    if let (Some(size), Some(interruption)) = (solutions.truncated(), solutions.interruption()) {
+        let reason = match interruption {
+            Interruption::Timeout => "timed out",
+            Interruption::Memory => "memory budget exceeded",
+            Interruption::Cancelled => "cancelled",
+        };
+        warn!(
+            "{} during size {}, sizes up to {} fully explored",
+            reason,
+            size,
+            size - 1
+        );
+    }
+}
+
+// Searches interrupted by the timeout or the memory budget exit with a failure once their
+// results are printed.
+fn exit_truncated(solutions: &Solutions) {
+    match solutions.interruption() {
+        Some(Interruption::Timeout | Interruption::Cancelled) => exit(Failure::Timeout.code()),
+        Some(Interruption::Memory) => exit(Failure::Memory.code()),
+        None => {}
+    }
+}
+
+fn metadata(
+    args: &SearchArgs,
+    solver: &Solver,
+    solutions: &Solutions,
+    target: Option<i64>,
+    targets: Option<Vec<i64>>,
+) -> JsonMetadata {
+    JsonMetadata {
+        max_number: args.max_number,
+        numbers: solver.config().numbers().to_vec(),
+        max_size: args.max_size.unwrap_or(solutions.size_searched()),
+        operations: solver.config().operations().symbols(),
+        target,
+        targets,
+        overflows: solutions.overflows(),
+        truncated_size: solutions.truncated(),
+        truncated_by: truncated_by(solutions),
+        degraded: solutions.degraded(),
+    }
+}
+
+// What stopped the search before it explored every size, if anything did.
+fn truncated_by(solutions: &Solutions) -> Option<&'static str> {
+    solutions
+        .interruption()
+        .map(|interruption| match interruption {
+            Interruption::Timeout => "timeout",
+            Interruption::Memory => "memory",
+            Interruption::Cancelled => "cancelled",
+        })
+}
+
+// Streams the solved values of `range` when requested, in place of the report.
+fn stream(
+    search: &SearchArgs,
+    output: &OutputArgs,
+    range: RangeInclusive<i64>,
+) -> Box<dyn Progress> {
+    let progress = progress(search);
+    if !output.stream {
+        return progress;
+    }
+    Box::new(Stream {
+        progress,
+        range,
+        syntax: output.syntax,
+        semantics: semantics(
+            search.width,
+            search.allow_truncating_division,
+            search.max_intermediate,
+            search.no_negative_intermediates,
+        ),
+        machines: output.machines,
+        order_operands: output.order_operands,
+        template: output
+            .template
+            .as_deref()
+            .and_then(|template| Template::parse(template).ok()),
+        out: Mutex::new(std::io::stdout()),
+    })
+}
+
+fn check_output(args: &OutputArgs) {
+    if matches!(args.syntax, Syntax::Tree) && (!matches!(args.format, Format::Text) || args.stream)
+    {
+        fail(
+            Failure::Arguments,
+            "the tree syntax is only supported by the text format",
+        );
+    }
+    if let Some(Err(e)) = args.export.as_deref().map(Export::parse) {
+        fail(Failure::Arguments, e);
+    }
+    if args.template.is_some() {
+        if !matches!(args.format, Format::Text)
+            || !matches!(args.report, Report::Solutions | Report::Hardest)
+        {
+            fail(
+                Failure::Arguments,
+                "templates are only supported by the text format of the solutions and hardest reports",
+            );
+        }
+        if matches!(args.syntax, Syntax::Tree) {
+            fail(
+                Failure::Arguments,
+                "the tree syntax cannot be used in templates",
+            );
+        }
+    }
+    if args.pretty && !matches!(args.format, Format::Text) {
+        fail(
+            Failure::Arguments,
+            "pretty expressions are only supported by the text format",
+        );
+    }
+    if let Some(Err(e)) = args.template.as_deref().map(Template::parse) {
+        fail(Failure::Arguments, e);
+    }
+}
+
+// Establishes the minimal sizes of the values up to the largest size of the solutions.
+fn minimal_sizes(solver: &Solver, solutions: &Solutions) -> MinimalSizes {
+    let largest = solutions
+        .iter()
+        .map(|(_, solution)| solution.size)
+        .max()
+        .unwrap_or(0);
+    MinimalSizes::new(solver.config(), largest)
+}
+
+// Establishes the minimal sizes of the values, and logs whether every solution has the minimal
+// size.
+fn prove(solver: &Solver, solutions: &Solutions) -> MinimalSizes {
+    let config = solver.config();
+    let proof = minimal_sizes(solver, solutions);
+    let unproved = solutions
+        .iter()
+        .filter(|(value, solution)| !proof.proves(*value, solution, config.semantics()))
+        .count();
+    if unproved == 0 {
+        info!("every size proved minimal");
+    } else {
+        warn!("{} values not proved minimal", unproved);
+    }
+    proof
+}
+
+// Prints the report of the `printed` values to the output, exiting if it cannot be written.
+fn report(
+    args: &OutputArgs,
+    solver: &Solver,
+    solutions: &Solutions,
+    printed: Box<dyn Iterator<Item = i64>>,
+    metadata: &JsonMetadata,
+) {
+    let semantics = solver.config().semantics();
+    let weighted = solver.config().objective().is_weighted();
+    let ordered = args.order_operands.then(|| {
+        let mut solutions = solutions.clone();
+        solutions.order_operands(semantics);
+        solutions
+    });
+    let solutions = ordered.as_ref().unwrap_or(solutions);
+    let annotations = Annotations {
+        proof: args.prove_minimal.then(|| prove(solver, solutions)),
+        machines: args.machines,
+        difficulty: args.difficulty,
+        sizes: (args.difficulty && weighted).then(|| minimal_sizes(solver, solutions)),
+        group: args.group_equivalent,
+        pretty: args.pretty,
+    };
+    // The solved values are left out of the report, and of the export, when only the unsolved
+    // ones are wanted.
+    let printed: Box<dyn Iterator<Item = i64>> = if args.only_unsolved {
+        Box::new(printed.filter(|value| solutions.get(*value).is_none()))
+    } else {
+        printed
+    };
+    // Every printed value is exported, whatever the report and its order.
+    let printed: Box<dyn Iterator<Item = i64>> = match args.export.as_deref().map(Export::parse) {
+        Some(Ok(destination)) => {
+            let values: Vec<i64> = printed.collect();
+            let exported = export::export(
+                &destination,
+                solutions,
+                values.iter().copied(),
+                metadata,
+                args.syntax,
+                solver.config(),
+                &annotations,
+            );
+            if let Err(e) = exported {
+                fail(Failure::Io, format!("unable to export results: {}", e));
             }
-            if op < composed.ops.len() {
-                let current_op = operation_dictionary.index(&composed.ops[op].borrow());
-                composed.ops[op].replace(operation_dictionary.operation(current_op + 1));
-            } else {
-                op_finished = true;
+            Box::new(values.into_iter())
+        }
+        _ => printed,
+    };
+    // The hardest values are the unsolved ones, then those with the largest and costliest
+    // solutions, keeping the order of the range or of the targets between ties.
+    let printed: Box<dyn Iterator<Item = i64>> = match args.report {
+        Report::Hardest => {
+            let mut values: Vec<i64> = printed.collect();
+            values.sort_by_key(|value| {
+                Reverse(
+                    solutions
+                        .get(*value)
+                        .map_or((usize::MAX, u64::MAX), |solution| {
+                            (solution.size, solution.cost)
+                        }),
+                )
+            });
+            values.truncate(args.hardest);
+            Box::new(values.into_iter())
+        }
+        _ => printed,
+    };
+    // Values are printed in the order of the range or of the targets unless sorted, unsolved
+    // values last when sorting by size. Sorting is stable, so ties keep that order.
+    let printed: Box<dyn Iterator<Item = i64>> = match args.sort {
+        None => printed,
+        Some(sort) => {
+            let mut values: Vec<i64> = printed.collect();
+            match sort {
+                Sort::Value => values.sort(),
+                Sort::Size => values.sort_by_key(|value| {
+                    solutions
+                        .get(*value)
+                        .map_or(usize::MAX, |solution| solution.size)
+                }),
+                Sort::Alternatives => values.sort_by_key(|value| {
+                    solutions.get(*value).map_or(0, |solution| solution.count)
+                }),
             }
+            Box::new(values.into_iter())
+        }
+    };
+    let template = args
+        .template
+        .as_deref()
+        .and_then(|template| Template::parse(template).ok());
+    let print = |out: &mut dyn Write| match (args.report, args.format) {
+        (Report::Missing, format) => print_missing(
+            out,
+            &missing(solutions, printed, args.group_ranges),
+            format,
+            metadata,
+        ),
+        (Report::Richness, format) => print_richness(
+            out,
+            solutions,
+            printed,
+            format,
+            solver.config().objective().is_weighted(),
+            metadata,
+        ),
+        (Report::Solutions | Report::Hardest, Format::Text) if template.is_some() => {
+            print_template(
+                out,
+                solutions,
+                printed,
+                template.as_ref().unwrap(),
+                args.syntax,
+                semantics,
+                &annotations,
+            )
+        }
+        (Report::Solutions | Report::Hardest, Format::Text) => print_text(
+            out,
+            solutions,
+            printed,
+            solver.config().objective().is_weighted(),
+            args.syntax,
+            semantics,
+            &annotations,
+        ),
+        (Report::Solutions | Report::Hardest, Format::Csv) => print_delimited(
+            out,
+            solutions,
+            printed,
+            ',',
+            args.syntax,
+            semantics,
+            &annotations,
+        ),
+        (Report::Solutions | Report::Hardest, Format::Tsv) => print_delimited(
+            out,
+            solutions,
+            printed,
+            '\t',
+            args.syntax,
+            semantics,
+            &annotations,
+        ),
+        (Report::Solutions | Report::Hardest, Format::Json) => print_json(
+            out,
+            solutions,
+            printed,
+            metadata,
+            args.syntax,
+            semantics,
+            &annotations,
+        ),
+        (Report::Solutions | Report::Hardest, Format::Dot) => {
+            print_dot(out, solutions, printed, semantics)
+        }
+        (Report::Solutions | Report::Hardest, Format::Latex) => {
+            print_latex(out, solutions, printed, &annotations)
+        }
+    };
+    let result = match &args.output {
+        Some(path) => write_atomically(path, print),
+        None => {
+            let stdout = std::io::stdout();
+            let mut out = BufWriter::new(stdout.lock());
+            print(&mut out).and_then(|_| out.flush())
+        }
+    };
+    if let Err(e) = result {
+        fail(Failure::Io, format!("unable to write results: {}", e));
+    }
+}
+
+fn enumerate(args: &EnumerateArgs) {
+    check_output(&args.output);
+    if args.count_only {
+        reachability::run(args);
+        return;
+    }
+    // The values past the end of a bounded range are not printed, so there is no need to search
+    // the sizes composing them once the range is covered.
+    let cover = args.print_to.map(|to| args.print_from.unwrap_or(1)..=to);
+    let from = args.print_from.unwrap_or(1);
+    let progress = stream(
+        &args.search,
+        &args.output,
+        from..=args.print_to.unwrap_or(i64::MAX),
+    );
+    let (solver, solutions) = search(
+        &args.search,
+        &args.checkpoints,
+        vec![],
+        cover,
+        matches!(args.output.report, Report::Richness),
+        progress.as_ref(),
+    );
+    if args.output.stream {
+        exit_truncated(&solutions);
+        return;
+    }
+    let to = args.print_to.unwrap_or(solutions.maximum_composed());
+    let metadata = metadata(&args.search, &solver, &solutions, None, None);
+    report(
+        &args.output,
+        &solver,
+        &solutions,
+        Box::new(from..to.saturating_add(1)),
+        &metadata,
+    );
+    exit_truncated(&solutions);
+}
+
+fn solve(args: &SolveArgs) {
+    check_output(&args.output);
+    let targets = match &args.targets_file {
+        Some(path) => read_targets(path),
+        None => args.targets.clone(),
+    };
+    let progress = stream(&args.search, &args.output, i64::MIN..=i64::MAX);
+    let (solver, solutions) = search(
+        &args.search,
+        &args.checkpoints,
+        targets.clone(),
+        None,
+        matches!(args.output.report, Report::Richness),
+        progress.as_ref(),
+    );
+    // The targets without a solution are reported as unsolved before exiting with a failure.
+    let unsolved = targets
+        .iter()
+        .any(|target| solutions.get(*target).is_none());
+    if args.output.stream {
+        exit_truncated(&solutions);
+        exit_unsolved(unsolved);
+        return;
+    }
+    // A single target is reported in the `target` field, several in the `targets` field.
+    let metadata = match targets.as_slice() {
+        [target] if args.targets_file.is_none() => {
+            metadata(&args.search, &solver, &solutions, Some(*target), None)
+        }
+        _ => metadata(
+            &args.search,
+            &solver,
+            &solutions,
+            None,
+            Some(targets.clone()),
+        ),
+    };
+    report(
+        &args.output,
+        &solver,
+        &solutions,
+        Box::new(targets.into_iter()),
+        &metadata,
+    );
+    exit_truncated(&solutions);
+    exit_unsolved(unsolved);
+}
+
+// Prints the alternative chosen for every target and the machines saved by building them
+// together.
+fn plan(args: &PlanArgs) {
+    let targets = match &args.targets_file {
+        Some(path) => read_targets(path),
+        None => args.targets.clone(),
+    };
+    let (solver, solutions) = search(
+        &args.search,
+        &args.checkpoints,
+        targets.clone(),
+        None,
+        false,
+        progress(&args.search).as_ref(),
+    );
+    let plan = Plan::new(&solutions, &targets, solver.config().semantics());
+    let chosen: HashMap<i64, &Expression> = plan
+        .expressions
+        .iter()
+        .map(|(target, expression)| (*target, expression))
+        .collect();
+    for target in targets.iter() {
+        match chosen.get(target) {
+            Some(expression) => println!("{} -> ({}) {}", target, expression.size(), expression),
+            None => println!("{} -> None", target),
         }
     }
+    if !plan.shared.is_empty() {
+        let shared: Vec<String> = plan.shared.iter().map(|value| value.to_string()).collect();
+        println!("shared values: {}", shared.join(", "));
+    }
+    println!(
+        "machines: {} ({} building each target alone, {} saved by sharing)",
+        plan.machines,
+        plan.independent_machines,
+        plan.saved()
+    );
+    exit_truncated(&solutions);
+    exit_unsolved(targets.iter().any(|target| !chosen.contains_key(target)));
+}
+
+fn exit_unsolved(unsolved: bool) {
+    if unsolved {
+        exit(Failure::Unsolved.code());
+    }
+}
+
+fn main() {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    failure::set_format_from(&args);
+    let cli = match Cli::try_parse_from(config::with_config(args)) {
+        Ok(cli) => cli,
+        Err(e) => failure::usage(e),
+    };
+    failure::set_format(cli.error_format);
+    logging::init(cli.verbose, cli.log_format);
+    match cli.command {
+        Command::Enumerate(args) => enumerate(&args),
+        Command::Solve(args) => solve(&args),
+        Command::Plan(args) => plan(&args),
+        Command::Verify(args) => verify::run(&args),
+        Command::Throughput(args) => throughput::run(&args),
+        Command::Analyze(args) => analyze::run(&args),
+        Command::Serve(args) => serve::run(&args),
+        Command::Stats(args) => stats::run(&args),
+        Command::Repl(search) => repl::run(&search),
+        Command::Query(args) => query::run(&args),
+        Command::Completions(args) => {
+            clap_complete::generate(
+                args.shell,
+                &mut Cli::command(),
+                "beltmatic-calc",
+                &mut std::io::stdout(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `(2^3)*4`, whose RPN `2 3 ^ 4 *` has an exponent followed by a number.
+    fn expression() -> Expression {
+        "(2^3)*4".parse().unwrap()
+    }
 
-    for v in 1..(maximum_composed + 1) {
-        println!(
-            "{} -> {}",
-            v,
-            dictionary
-                .get(&v)
-                .map(|(size, options)| format!("({}) {:?}", size, options))
-                .unwrap_or("None".to_string())
+    fn pretty(syntax: Syntax) -> String {
+        syntax.render_as(&expression(), Semantics::default(), true)
+    }
+
+    #[test]
+    fn pretty_infix_uses_the_symbols_of_mathematics() {
+        assert_eq!(pretty(Syntax::Infix), "((2³)×4)");
+        assert_eq!(
+            Syntax::Infix.render_as(
+                &"(10-6)/2^(1+1)".parse().unwrap(),
+                Semantics::default(),
+                true
+            ),
+            "((10-6)÷(2^(1+1)))"
         );
     }
+
+    #[test]
+    fn pretty_steps_use_the_symbols_of_mathematics() {
+        assert_eq!(pretty(Syntax::Steps), "step 1: 2³ = 8; step 2: 8 × 4 = 32");
+    }
+
+    #[test]
+    fn pretty_tree_falls_back_to_the_pretty_infix() {
+        assert_eq!(pretty(Syntax::Tree), "((2³)×4)");
+    }
+
+    #[test]
+    fn pretty_rpn_is_unchanged() {
+        assert_eq!(pretty(Syntax::Rpn), "2 3 ^ 4 *");
+    }
+
+    #[test]
+    fn pretty_call_is_unchanged() {
+        assert_eq!(pretty(Syntax::Call), "mul(pow(2, 3), 4)");
+    }
+
+    #[test]
+    fn pretty_sexpr_is_unchanged() {
+        assert_eq!(pretty(Syntax::Sexpr), "(* (^ 2 3) 4)");
+    }
 }
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+
+use crate::{
+    dynamic,
+    ida::{bound, complements, reach},
+    progress::Unreported,
+    solver::Deadline,
+    Expression, Progress, Solution, Solutions, SolverConfig,
+};
+
+// Searches the targets joining two halves: every value reachable with up to half of the
+// maximum size, negative values included, is enumerated into a table, with its minimal
+// alternatives, and a target is then split into a value of the table and the complement the
+// other operand must evaluate to, e.g. `target - x` or `target / x`. Complements in the table
+// are looked up; those needing more leaves than the table holds are joined the same way in turn. The subexpressions of a minimal
+// expression are minimal themselves, so joining minimal operands of sizes adding up to each
+// size in increasing order finds every minimal alternative, as the exhaustive strategies do.
+//
+// Unary operations, limits, operation costs and truncating division are not supported by the
+// join, and are searched with the dynamic strategy instead.
+pub(crate) fn search(config: &SolverConfig, progress: &dyn Progress) -> Solutions {
+    if config.targets.is_empty()
+        || !config.operations.unary_operations().is_empty()
+        || config.limits.is_limited()
+        || config.objective.is_weighted()
+        || config.semantics.truncating_division
+    {
+        return dynamic::search(config, progress);
+    }
+    let half = config.max_size.div_ceil(2);
+    let mut table_config = config.clone();
+    table_config.max_size = half;
+    table_config.targets = vec![];
+    table_config.cover = None;
+    table_config.count_distinct = false;
+    let table = dynamic::search_values(&table_config, &Unreported(progress), &|_| true);
+    let mut joiner = Joiner::new(config, half, &table);
+    let mut dictionary = HashMap::new();
+    let mut size_searched = table.size_searched();
+    let mut truncated = table.truncated().zip(table.interruption());
+    let mut targets = config.targets.clone();
+    targets.sort();
+    targets.dedup();
+    if truncated.is_none() {
+        for target in targets {
+            let solution = joiner.solve(target, config.max_size);
+            if joiner.timed_out {
+                truncated = Some((joiner.size, joiner.deadline.interruption()));
+                break;
+            }
+            if let Some(mut solution) = solution {
+                solution.finish(config);
+                progress.solved(target, &solution);
+                dictionary.insert(target, solution);
+            }
+        }
+        if truncated.is_none() {
+            size_searched = config.max_size;
+        }
+    }
+    let maximum_composed = dictionary.keys().copied().max().unwrap_or(1);
+    Solutions::new(
+        dictionary,
+        maximum_composed,
+        table.overflows(),
+        size_searched,
+        truncated,
+        table.degraded(),
+    )
+}
+
+// What is known of a value out of the table.
+enum Known {
+    Solved(Solution),
+    // No expression has at most this many leaves.
+    Unreachable(usize),
+}
+
+struct Joiner<'a> {
+    config: &'a SolverConfig,
+    half: usize,
+    table: HashMap<i64, &'a Solution>,
+    // layers[size] lists the values of the table whose minimal number of leaves is `size`.
+    layers: Vec<Vec<i64>>,
+    reach: Vec<u64>,
+    known: HashMap<i64, Known>,
+    limit: Option<usize>,
+    deadline: Deadline,
+    timed_out: bool,
+    // The size being joined when the search timed out.
+    size: usize,
+}
+
+impl<'a> Joiner<'a> {
+    fn new(config: &'a SolverConfig, half: usize, table: &'a Solutions) -> Joiner<'a> {
+        let mut layers = vec![vec![]; half + 1];
+        for (value, solution) in table.iter() {
+            layers[solution.size].push(value);
+        }
+        for layer in layers.iter_mut() {
+            layer.sort();
+        }
+        Joiner {
+            config,
+            half,
+            table: table.iter().collect(),
+            layers,
+            reach: reach(config),
+            known: HashMap::new(),
+            limit: if config.keeps_all_alternatives() {
+                None
+            } else {
+                config.max_alternatives
+            },
+            deadline: config.deadline(),
+            timed_out: false,
+            size: 0,
+        }
+    }
+
+    // The minimal alternatives of `target`, if it has an expression with at most `leaves`
+    // leaves.
+    fn solve(&mut self, target: i64, leaves: usize) -> Option<Solution> {
+        if let Some(solution) = self.table.get(&target) {
+            return (solution.size <= leaves).then(|| (*solution).clone());
+        }
+        // Values that operands of any value can combine into, like `0 * x`, are left to the
+        // table.
+        if target.unsigned_abs() <= 1 {
+            return None;
+        }
+        let mut from = (self.half + 1).max(bound(&self.reach, target));
+        match self.known.get(&target) {
+            Some(Known::Solved(solution)) => {
+                return (solution.size <= leaves).then(|| solution.clone());
+            }
+            Some(Known::Unreachable(unreachable)) if *unreachable >= leaves => return None,
+            Some(Known::Unreachable(unreachable)) => from = from.max(unreachable + 1),
+            None => {}
+        }
+        for size in from..(leaves + 1) {
+            let solution = self.join(target, size);
+            if self.timed_out {
+                return None;
+            }
+            if solution.count > 0 {
+                self.known.insert(target, Known::Solved(solution.clone()));
+                return Some(solution);
+            }
+        }
+        self.known.insert(target, Known::Unreachable(leaves));
+        None
+    }
+
+    // The alternatives of `target` with exactly `size` leaves combining minimal operands.
+    fn join(&mut self, target: i64, size: usize) -> Solution {
+        let mut solution = Solution::empty(size, size as u64 - 1);
+        if self.deadline.expired() {
+            self.timed_out = true;
+            self.size = size;
+            return solution;
+        }
+        for small_size in 1..(self.half.min(size - 1) + 1) {
+            let other_size = size - small_size;
+            for index in 0..self.layers[small_size].len() {
+                let small = self.layers[small_size][index];
+                let mut candidates = complements(self.config, target, small);
+                // Commutative operations only yield the small operand on the left.
+                candidates.extend(
+                    candidates
+                        .clone()
+                        .into_iter()
+                        .filter(|(operation, _, _)| operation.is_commutative())
+                        .map(|(operation, _, other)| (operation, false, other)),
+                );
+                candidates.sort();
+                candidates.dedup();
+                for (operation, small_left, other) in candidates {
+                    // Pairs of operands both in the table are joined once, from the left.
+                    if !small_left && other_size <= self.half {
+                        continue;
+                    }
+                    if bound(&self.reach, other) > other_size {
+                        continue;
+                    }
+                    let (left, right) = if small_left {
+                        (small, other)
+                    } else {
+                        (other, small)
+                    };
+                    if operation.apply(left, right, self.config.semantics) != Ok(target) {
+                        continue;
+                    }
+                    let Some(operand) = self.solve(other, other_size) else {
+                        if self.timed_out {
+                            return solution;
+                        }
+                        continue;
+                    };
+                    if operand.size != other_size {
+                        continue;
+                    }
+                    let small: &Solution = self.table[&small];
+                    let (left, right) = if small_left {
+                        (small, &operand)
+                    } else {
+                        (&operand, small)
+                    };
+                    solution.count = solution
+                        .count
+                        .saturating_add(left.count.saturating_mul(right.count));
+                    for l in left.alternatives.iter() {
+                        for r in right.alternatives.iter() {
+                            if self
+                                .limit
+                                .is_some_and(|limit| solution.alternatives.len() >= limit)
+                            {
+                                break;
+                            }
+                            solution.alternatives.push(Expression::binary(
+                                operation,
+                                l.clone(),
+                                r.clone(),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        solution
+    }
+}
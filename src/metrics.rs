@@ -0,0 +1,113 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+// The counters of a server, rendered in the Prometheus text format. Rates, like the candidates
+// explored per second, are left to the queries, e.g.
+// `rate(beltmatic_candidates_explored_total[1m])`.
+#[derive(Default)]
+pub(crate) struct Metrics {
+    // The requests answered, by route and status.
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    // Values asked that the search done at startup solves, or does not.
+    hits: AtomicU64,
+    misses: AtomicU64,
+    candidates: AtomicU64,
+    jobs: AtomicU64,
+    in_flight: AtomicU64,
+}
+
+impl Metrics {
+    pub(crate) fn request(&self, route: &str, status: u16) {
+        *self
+            .requests
+            .lock()
+            .unwrap()
+            .entry((route.to_string(), status))
+            .or_default() += 1;
+    }
+
+    pub(crate) fn lookup(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn explored(&self, candidates: u64) {
+        self.candidates.fetch_add(candidates, Ordering::Relaxed);
+    }
+
+    pub(crate) fn job_started(&self) {
+        self.jobs.fetch_add(1, Ordering::Relaxed);
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn job_done(&self) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn render(&self) -> String {
+        let mut text = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: Vec<(String, u64)>| {
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} {}", name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(text, "{}{} {}", name, labels, value);
+            }
+        };
+        let requests = self
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((route, status), count)| {
+                (
+                    format!("{{route=\"{}\",status=\"{}\"}}", route, status),
+                    *count,
+                )
+            })
+            .collect();
+        let load = |counter: &AtomicU64| vec![(String::new(), counter.load(Ordering::Relaxed))];
+        metric(
+            "beltmatic_requests_total",
+            "counter",
+            "Requests answered, by route and status.",
+            requests,
+        );
+        metric(
+            "beltmatic_cache_hits_total",
+            "counter",
+            "Values asked that the search done at startup solves.",
+            load(&self.hits),
+        );
+        metric(
+            "beltmatic_cache_misses_total",
+            "counter",
+            "Values asked that the search done at startup does not solve.",
+            load(&self.misses),
+        );
+        metric(
+            "beltmatic_candidates_explored_total",
+            "counter",
+            "Candidate expressions evaluated by the jobs.",
+            load(&self.candidates),
+        );
+        metric(
+            "beltmatic_jobs_total",
+            "counter",
+            "Jobs submitted.",
+            load(&self.jobs),
+        );
+        metric(
+            "beltmatic_jobs_in_flight",
+            "gauge",
+            "Jobs still running.",
+            load(&self.in_flight),
+        );
+        text
+    }
+}
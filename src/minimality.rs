@@ -0,0 +1,121 @@
+use rayon::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::RangeInclusive,
+};
+
+use crate::{
+    limits::{Root, Usage, Uses},
+    Semantics, Solution, SolverConfig,
+};
+
+// The minimal number of leaves of every value reachable under the operations, semantics,
+// limits, adjacency constraints and required numbers of a search, established independently of
+// the solvers by building the set of values reachable with each number of leaves. Every pair of
+// values of two sets is combined with every operation, so a value missing from the sets of the
+// smaller sizes cannot be built with fewer leaves.
+pub struct MinimalSizes {
+    sizes: HashMap<i64, usize>,
+}
+
+impl MinimalSizes {
+    // Builds the sets of values reachable with up to `max_size` leaves.
+    pub fn new(config: &SolverConfig, max_size: usize) -> MinimalSizes {
+        let operations = &config.operations;
+        let usage = Usage::new(config);
+        let usage = &usage;
+        let mut layers: Vec<HashSet<(i64, Uses, Root)>> = vec![HashSet::new()];
+        let mut sizes = HashMap::new();
+        for size in 1..(max_size + 1) {
+            let mut layer: HashSet<(i64, Uses, Root)> = HashSet::new();
+            if size == 1 {
+                layer.extend(
+                    config
+                        .numbers
+                        .iter()
+                        .filter_map(|&n| Some((n, usage.number(n)?, 0))),
+                );
+            }
+            for split in config.limits.splits(size) {
+                let rights = &layers[size - split];
+                let combined: Vec<(i64, Uses, Root)> = layers[split]
+                    .par_iter()
+                    .flat_map_iter(|&(left, left_uses, left_root)| {
+                        rights
+                            .iter()
+                            .flat_map(move |&(right, right_uses, right_root)| {
+                                operations.operations().iter().enumerate().filter_map(
+                                    move |(index, operation)| {
+                                        if !usage.feeds(left_root, index)
+                                            || !usage.feeds(right_root, index)
+                                        {
+                                            return None;
+                                        }
+                                        let uses = usage
+                                            .combine(left_uses, right_uses)
+                                            .and_then(|uses| {
+                                                usage.combine(uses, usage.binary(index))
+                                            })
+                                            .and_then(|uses| usage.deepen(uses))?;
+                                        let value =
+                                            operation.apply(left, right, config.semantics).ok()?;
+                                        Some((value, uses, usage.root(index)))
+                                    },
+                                )
+                            })
+                    })
+                    .collect();
+                layer.extend(combined);
+            }
+            // Negating twice gives back the operand, so unary operations are applied once.
+            let unary: Vec<(i64, Uses, Root)> = layer
+                .iter()
+                .flat_map(|&(operand, uses, _)| {
+                    operations.unary_operations().iter().enumerate().filter_map(
+                        move |(index, operation)| {
+                            let uses = usage.deepen(usage.combine(uses, usage.unary(index))?)?;
+                            let value = operation.apply(operand, config.semantics).ok()?;
+                            Some((value, uses, 0))
+                        },
+                    )
+                })
+                .collect();
+            layer.extend(unary);
+            for &(value, uses, _) in layer.iter() {
+                if usage.satisfies(uses) {
+                    sizes.entry(value).or_insert(size);
+                }
+            }
+            layers.push(layer);
+        }
+        MinimalSizes { sizes }
+    }
+
+    pub fn get(&self, value: i64) -> Option<usize> {
+        self.sizes.get(&value).copied()
+    }
+
+    // The number of values of `range` reachable with each minimal size, for reachability studies
+    // that need no expression. Sizes reaching none of them are left out.
+    pub fn counts(&self, range: RangeInclusive<i64>) -> BTreeMap<usize, usize> {
+        let mut counts = BTreeMap::new();
+        for (_, size) in self.sizes.iter().filter(|(value, _)| range.contains(value)) {
+            *counts.entry(*size).or_default() += 1;
+        }
+        counts
+    }
+
+    // The largest value reachable, if any.
+    pub fn largest(&self) -> Option<i64> {
+        self.sizes.keys().copied().max()
+    }
+
+    // Whether the size of the solution is the minimal number of leaves of `value`, every
+    // alternative kept evaluating to it with that many leaves.
+    pub fn proves(&self, value: i64, solution: &Solution, semantics: Semantics) -> bool {
+        self.get(value) == Some(solution.size)
+            && solution.alternatives.iter().all(|alternative| {
+                alternative.size() == solution.size && alternative.eval(semantics) == Ok(value)
+            })
+    }
+}
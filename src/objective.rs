@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use crate::{Expression, Operation, OperationDictionary, UnaryOperation};
+
+// Solutions are ranked by the total cost of their operations, and then by their size. With the
+// default unit costs the cost of an expression without unary operations is its size minus one,
+// so the objective reduces to minimising the number of leaves; unary operations add their cost
+// without adding leaves. Numbers cost nothing unless given a cost, e.g. for the extractors far
+// from the factory, which every leaf of the number adds.
+#[derive(Clone, Debug, Default)]
+pub struct Objective {
+    costs: HashMap<Operation, u64>,
+    unary_costs: HashMap<UnaryOperation, u64>,
+    number_costs: HashMap<i64, u64>,
+}
+
+impl Objective {
+    pub fn weighted(
+        costs: HashMap<Operation, u64>,
+        unary_costs: HashMap<UnaryOperation, u64>,
+    ) -> Objective {
+        Objective {
+            costs,
+            unary_costs,
+            number_costs: HashMap::new(),
+        }
+    }
+
+    pub fn with_number_costs(mut self, number_costs: HashMap<i64, u64>) -> Objective {
+        self.number_costs = number_costs;
+        self
+    }
+
+    pub fn is_weighted(&self) -> bool {
+        self.costs
+            .values()
+            .chain(self.unary_costs.values())
+            .any(|cost| *cost != 1)
+            || self.number_costs.values().any(|cost| *cost != 0)
+    }
+
+    pub fn cost(&self, operation: Operation) -> u64 {
+        self.costs.get(&operation).copied().unwrap_or(1)
+    }
+
+    pub fn unary_cost(&self, operation: UnaryOperation) -> u64 {
+        self.unary_costs.get(&operation).copied().unwrap_or(1)
+    }
+
+    pub fn number_cost(&self, number: i64) -> u64 {
+        self.number_costs.get(&number).copied().unwrap_or(0)
+    }
+
+    pub(crate) fn has_number_costs(&self) -> bool {
+        !self.number_costs.is_empty()
+    }
+
+    pub fn expression_cost(&self, expression: &Expression) -> u64 {
+        match expression {
+            Expression::Number(number) => self.number_cost(*number),
+            Expression::Binary(operation, left, right) => {
+                self.cost(*operation) + self.expression_cost(left) + self.expression_cost(right)
+            }
+            Expression::Unary(operation, operand) => {
+                self.unary_cost(*operation) + self.expression_cost(operand)
+            }
+        }
+    }
+
+    // A lower bound of the cost of the expressions combining `size` numbers.
+    pub(crate) fn minimum_cost(
+        &self,
+        size: usize,
+        operations: &OperationDictionary,
+        numbers: &[i64],
+    ) -> u64 {
+        let cheapest = operations
+            .operations()
+            .iter()
+            .map(|operation| self.cost(*operation))
+            .min()
+            .unwrap_or(0);
+        let cheapest_number = numbers
+            .iter()
+            .map(|number| self.number_cost(*number))
+            .min()
+            .unwrap_or(0);
+        (size as u64 - 1) * cheapest + size as u64 * cheapest_number
+    }
+}
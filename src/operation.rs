@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Debug, Serialize, Deserialize)]
+pub enum Operation {
+    ADD,
+    MULT,
+    DIV,
+    SUB,
+    EXP,
+    CONCAT,
+}
+
+// Operations applied to a single operand, which do not change the number of values combined.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Debug, Serialize, Deserialize)]
+pub enum UnaryOperation {
+    NEG,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum Width {
+    #[default]
+    Bits32,
+    Bits64,
+}
+
+impl Width {
+    fn check(&self, value: Option<i64>) -> Result<i64, EvalError> {
+        match (self, value) {
+            (Self::Bits32, Some(value)) if i32::try_from(value).is_ok() => Ok(value),
+            (Self::Bits64, Some(value)) => Ok(value),
+            _ => Err(EvalError::Overflow),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Default, Serialize, Deserialize)]
+pub struct Semantics {
+    pub width: Width,
+    pub truncating_division: bool,
+    // Intermediate values larger than this, in absolute value, cannot be carried by the belts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_intermediate: Option<u64>,
+    // Belts cannot carry negative values either.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_negative_intermediates: bool,
+}
+
+impl Semantics {
+    fn check(&self, value: Option<i64>) -> Result<i64, EvalError> {
+        let value = self.width.check(value)?;
+        if self.no_negative_intermediates && value < 0 {
+            return Err(EvalError::Negative);
+        }
+        match self.max_intermediate {
+            Some(max) if value.unsigned_abs() > max => Err(EvalError::TooLarge),
+            _ => Ok(value),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum EvalError {
+    DivisionByZero,
+    InexactDivision,
+    NegativeExponent,
+    NegativeConcatenation,
+    Overflow,
+    TooLarge,
+    Negative,
+    // Skipped by the solvers as it cannot be part of a minimal expression, see
+    // `SolverConfig::is_wasteful`. Evaluating an expression never fails with it.
+    Wasteful,
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DivisionByZero => write!(f, "division by zero"),
+            Self::InexactDivision => write!(f, "inexact division"),
+            Self::NegativeExponent => write!(f, "negative exponent"),
+            Self::NegativeConcatenation => write!(f, "concatenation of a negative number"),
+            Self::Overflow => write!(f, "overflow"),
+            Self::TooLarge => write!(f, "value larger than the maximum intermediate"),
+            Self::Negative => write!(f, "negative value"),
+            Self::Wasteful => write!(f, "wasteful operation"),
+        }
+    }
+}
+
+impl Operation {
+    // Operations are given by their symbol or by their name, which needs no quoting in shells
+    // and config files, e.g. `*` or `mul`.
+    pub fn parse(symbol: &str) -> Option<Operation> {
+        match symbol {
+            "+" | "add" => Some(Operation::ADD),
+            "-" | "sub" => Some(Operation::SUB),
+            "*" | "mul" => Some(Operation::MULT),
+            "/" | "div" => Some(Operation::DIV),
+            "^" | "pow" => Some(Operation::EXP),
+            "||" | "concat" => Some(Operation::CONCAT),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ADD => "add",
+            Self::SUB => "sub",
+            Self::MULT => "mul",
+            Self::DIV => "div",
+            Self::EXP => "pow",
+            Self::CONCAT => "concat",
+        }
+    }
+
+    // Operands of commutative operations can be swapped, and chains of them can be
+    // parenthesised in any order.
+    pub fn is_commutative(&self) -> bool {
+        matches!(self, Self::ADD | Self::MULT)
+    }
+
+    // Whether the operation gives back one of its operands, like `x*1` or `x+0`.
+    pub(crate) fn is_identity(&self, l: i64, r: i64) -> bool {
+        match self {
+            Self::ADD => l == 0 || r == 0,
+            Self::SUB => r == 0,
+            Self::MULT => l == 1 || r == 1,
+            Self::DIV | Self::EXP => r == 1,
+            Self::CONCAT => l == 0 && r >= 0,
+        }
+    }
+
+    pub fn apply(&self, l: i64, r: i64, semantics: Semantics) -> Result<i64, EvalError> {
+        match self {
+            Self::ADD => semantics.check(l.checked_add(r)),
+            Self::SUB => semantics.check(l.checked_sub(r)),
+            Self::MULT => semantics.check(l.checked_mul(r)),
+            Self::DIV if r == 0 => Err(EvalError::DivisionByZero),
+            Self::DIV
+                if !semantics.truncating_division
+                    && l.checked_rem(r).is_some_and(|rem| rem != 0) =>
+            {
+                Err(EvalError::InexactDivision)
+            }
+            Self::DIV => semantics.check(l.checked_div(r)),
+            Self::EXP => match u32::try_from(r) {
+                Ok(r) => semantics.check(l.checked_pow(r)),
+                Err(_) => Err(EvalError::NegativeExponent),
+            },
+            // The digits of `r` are appended to those of `l`, e.g. `3 || 7 = 37`.
+            Self::CONCAT if l < 0 || r < 0 => Err(EvalError::NegativeConcatenation),
+            Self::CONCAT => {
+                let shift = 10i64.checked_pow(r.checked_ilog10().unwrap_or(0) + 1);
+                semantics.check(shift.and_then(|shift| l.checked_mul(shift)?.checked_add(r)))
+            }
+        }
+    }
+}
+
+impl UnaryOperation {
+    pub fn parse(symbol: &str) -> Option<UnaryOperation> {
+        match symbol {
+            "neg" => Some(UnaryOperation::NEG),
+            _ => None,
+        }
+    }
+
+    // The prefix written before the operand in infix notation.
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Self::NEG => "-",
+        }
+    }
+
+    pub fn apply(&self, v: i64, semantics: Semantics) -> Result<i64, EvalError> {
+        match self {
+            Self::NEG => semantics.check(v.checked_neg()),
+        }
+    }
+}
+
+impl Display for UnaryOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NEG => write!(f, "neg"),
+        }
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ADD => write!(f, "+"),
+            Self::SUB => write!(f, "-"),
+            Self::MULT => write!(f, "*"),
+            Self::DIV => write!(f, "/"),
+            Self::EXP => write!(f, "^"),
+            Self::CONCAT => write!(f, "||"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OperationDictionary {
+    operations: Vec<Operation>,
+    unary_operations: Vec<UnaryOperation>,
+}
+
+impl OperationDictionary {
+    // Operations given more than once, e.g. as `*` and `mul`, are combined once.
+    pub fn new(options: &[String]) -> Option<OperationDictionary> {
+        let mut operations: Vec<Operation> = vec![];
+        let mut unary_operations: Vec<UnaryOperation> = vec![];
+        let mut error = false;
+        for option in options.iter() {
+            match (Operation::parse(option), UnaryOperation::parse(option)) {
+                (Some(operation), _) => {
+                    if !operations.contains(&operation) {
+                        operations.push(operation);
+                    }
+                }
+                (None, Some(operation)) => {
+                    if !unary_operations.contains(&operation) {
+                        unary_operations.push(operation);
+                    }
+                }
+                (None, None) => {
+                    error = true;
+                }
+            }
+        }
+        if error {
+            None
+        } else {
+            Some(OperationDictionary {
+                operations,
+                unary_operations,
+            })
+        }
+    }
+
+    pub fn operation(&self, index: usize) -> Operation {
+        self.operations[index]
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    pub fn unary_operations(&self) -> &[UnaryOperation] {
+        &self.unary_operations
+    }
+
+    // The symbols of the binary operations followed by those of the unary ones.
+    pub fn symbols(&self) -> Vec<String> {
+        self.operations
+            .iter()
+            .map(|operation| operation.to_string())
+            .chain(
+                self.unary_operations
+                    .iter()
+                    .map(|operation| operation.to_string()),
+            )
+            .collect()
+    }
+}
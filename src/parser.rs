@@ -0,0 +1,131 @@
+use std::{fmt::Display, iter::Peekable, str::CharIndices, str::FromStr};
+
+use crate::{Expression, Operation, UnaryOperation};
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+// Concatenation binds tighter than exponentiation, which binds tighter than multiplication and
+// division, which bind tighter than addition and subtraction.
+fn precedence(operation: Operation) -> u8 {
+    match operation {
+        Operation::ADD | Operation::SUB => 1,
+        Operation::MULT | Operation::DIV => 2,
+        Operation::EXP => 3,
+        Operation::CONCAT => 4,
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Parser<'a> {
+        Parser {
+            input,
+            chars: input.char_indices().peekable(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<(usize, char)> {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn error(&mut self, message: &str) -> ParseError {
+        ParseError {
+            position: self
+                .peek()
+                .map_or(self.input.len(), |(position, _)| position),
+            message: message.to_string(),
+        }
+    }
+
+    // Precedence climbing: operators are left associative except exponentiation.
+    fn expression(&mut self, min_precedence: u8) -> Result<Expression, ParseError> {
+        let mut left = self.operand()?;
+        while let Some((_, symbol)) = self.peek() {
+            let symbol = match symbol {
+                '|' => "||".to_string(),
+                symbol => symbol.to_string(),
+            };
+            let operation = match Operation::parse(&symbol) {
+                Some(operation) if precedence(operation) >= min_precedence => operation,
+                _ => break,
+            };
+            self.chars.next();
+            if operation == Operation::CONCAT && self.chars.next_if(|(_, c)| *c == '|').is_none() {
+                return Err(self.error("expected '||'"));
+            }
+            let right = if operation == Operation::EXP {
+                self.expression(precedence(operation))?
+            } else {
+                self.expression(precedence(operation) + 1)?
+            };
+            left = Expression::binary(operation, left, right);
+        }
+        Ok(left)
+    }
+
+    fn operand(&mut self) -> Result<Expression, ParseError> {
+        match self.peek() {
+            Some((_, '(')) => {
+                self.chars.next();
+                let expression = self.expression(0)?;
+                match self.peek() {
+                    Some((_, ')')) => {
+                        self.chars.next();
+                        Ok(expression)
+                    }
+                    _ => Err(self.error("expected ')'")),
+                }
+            }
+            // Negation binds tighter than every operation but exponentiation: `-2^2` is `-(2^2)`.
+            Some((_, '-')) => {
+                self.chars.next();
+                let operand = self.expression(precedence(Operation::EXP))?;
+                Ok(Expression::unary(UnaryOperation::NEG, operand))
+            }
+            Some((start, c)) if c.is_ascii_digit() => {
+                let mut end = start;
+                while let Some((position, c)) = self.chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                    end = position + c.len_utf8();
+                }
+                self.input[start..end]
+                    .parse()
+                    .map(Expression::Number)
+                    .map_err(|_| ParseError {
+                        position: start,
+                        message: "number too large".to_string(),
+                    })
+            }
+            Some((_, c)) => Err(self.error(&format!("unexpected '{}'", c))),
+            None => Err(self.error("unexpected end of expression")),
+        }
+    }
+}
+
+// Parses infix expressions of non-negative integers, e.g. `(3*7+2)*11` or `-(3-7)*5`.
+impl FromStr for Expression {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Expression, ParseError> {
+        let mut parser = Parser::new(s);
+        let expression = parser.expression(0)?;
+        match parser.peek() {
+            Some((_, c)) => Err(parser.error(&format!("unexpected '{}'", c))),
+            None => Ok(expression),
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{Expression, Semantics, Solutions};
+
+// Builds several targets at once, sharing the machines that produce the same intermediate
+// value: the output of a machine can be split to feed every machine needing its value, so a
+// plan needs one machine per distinct value produced. An alternative is chosen for every target
+// so that their expressions have as many values in common as possible, starting from the
+// alternatives needing the fewest machines alone and switching the alternative of one target at
+// a time while the plan needs fewer machines.
+pub struct Plan {
+    // The alternative chosen for every solved target, in the order of the targets.
+    pub expressions: Vec<(i64, Expression)>,
+    // The values produced for more than one target, in increasing order.
+    pub shared: Vec<i64>,
+    // The machines of the plan, each value being produced once.
+    pub machines: usize,
+    // The machines needed to build each target on its own.
+    pub independent_machines: usize,
+}
+
+impl Plan {
+    pub fn new(solutions: &Solutions, targets: &[i64], semantics: Semantics) -> Plan {
+        let mut seen = HashSet::new();
+        let alternatives: Vec<(i64, &[Expression])> = targets
+            .iter()
+            .filter(|target| seen.insert(**target))
+            .filter_map(|target| {
+                let solution = solutions.get(*target)?;
+                (!solution.alternatives.is_empty())
+                    .then_some((*target, solution.alternatives.as_slice()))
+            })
+            .collect();
+        let mut chosen: Vec<usize> = alternatives
+            .iter()
+            .map(|(_, expressions)| {
+                (0..expressions.len())
+                    .min_by_key(|index| machines([&expressions[*index]], semantics))
+                    .unwrap()
+            })
+            .collect();
+        let independent_machines = alternatives
+            .iter()
+            .zip(chosen.iter())
+            .map(|((_, expressions), index)| machines([&expressions[*index]], semantics))
+            .sum();
+        let plan_machines = |chosen: &[usize]| {
+            machines(
+                alternatives
+                    .iter()
+                    .zip(chosen.iter())
+                    .map(|((_, expressions), index)| &expressions[*index]),
+                semantics,
+            )
+        };
+        let mut best = plan_machines(&chosen);
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for target in 0..alternatives.len() {
+                for index in 0..alternatives[target].1.len() {
+                    let previous = chosen[target];
+                    chosen[target] = index;
+                    let machines = plan_machines(&chosen);
+                    if machines < best {
+                        best = machines;
+                        improved = true;
+                    } else {
+                        chosen[target] = previous;
+                    }
+                }
+            }
+        }
+        let expressions: Vec<(i64, Expression)> = alternatives
+            .iter()
+            .zip(chosen.iter())
+            .map(|((target, expressions), index)| (*target, expressions[*index].clone()))
+            .collect();
+        let mut targets_of: HashMap<i64, usize> = HashMap::new();
+        for (_, expression) in expressions.iter() {
+            let mut produced = HashSet::new();
+            expression.collect_produced(semantics, &mut produced);
+            for value in produced {
+                *targets_of.entry(value).or_default() += 1;
+            }
+        }
+        let mut shared: Vec<i64> = targets_of
+            .into_iter()
+            .filter(|(_, targets)| *targets > 1)
+            .map(|(value, _)| value)
+            .collect();
+        shared.sort();
+        Plan {
+            expressions,
+            shared,
+            machines: best,
+            independent_machines,
+        }
+    }
+
+    // The machines saved by sharing values between the targets.
+    pub fn saved(&self) -> usize {
+        self.independent_machines - self.machines
+    }
+}
+
+// The machines needed to build all the expressions, producing every value once.
+fn machines<'a>(
+    expressions: impl IntoIterator<Item = &'a Expression>,
+    semantics: Semantics,
+) -> usize {
+    let mut produced = HashSet::new();
+    for expression in expressions {
+        expression.collect_produced(semantics, &mut produced);
+    }
+    produced.len()
+}
+
+impl Expression {
+    // Collects the values produced by the operations of the expression. The operands of a value
+    // already produced are not needed again, and are skipped.
+    fn collect_produced(&self, semantics: Semantics, produced: &mut HashSet<i64>) {
+        let operands = match self {
+            Expression::Number(_) => return,
+            Expression::Binary(_, left, right) => vec![left, right],
+            Expression::Unary(_, operand) => vec![operand],
+        };
+        if let Ok(value) = self.eval(semantics) {
+            if !produced.insert(value) {
+                return;
+            }
+        }
+        for operand in operands {
+            operand.collect_produced(semantics, produced);
+        }
+    }
+}
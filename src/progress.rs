@@ -0,0 +1,139 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+use crate::{Solution, SolverConfig};
+
+// Hooks to follow a running search. Sizes are searched in increasing order; within a size the
+// search reports the number of candidates explored, out of the total announced at the start,
+// and the number of distinct values solved so far. At the end of every size each value whose
+// minimal solution can no longer change is reported once as solved.
+pub trait Progress: Sync {
+    fn size_started(&self, size: usize, total: u64);
+    fn explored(&self, candidates: u64);
+    fn discovered(&self, values: usize);
+    fn solved(&self, value: i64, solution: &Solution);
+    fn size_finished(&self, size: usize);
+}
+
+pub struct NoProgress;
+
+impl Progress for NoProgress {
+    fn size_started(&self, _size: usize, _total: u64) {}
+
+    fn explored(&self, _candidates: u64) {}
+
+    fn discovered(&self, _values: usize) {}
+
+    fn solved(&self, _value: i64, _solution: &Solution) {}
+
+    fn size_finished(&self, _size: usize) {}
+}
+
+// A simpler way to follow a search from an application, e.g. to drive its own progress bar:
+// every hook does nothing unless overridden, and `on_progress` is given the candidates explored
+// so far out of the total of the size, rather than the ones explored since the last report.
+pub trait SolverObserver: Sync {
+    fn on_size_start(&self, _size: usize, _total: u64) {}
+    fn on_progress(&self, _size: usize, _explored: u64, _total: u64) {}
+    fn on_value_found(&self, _value: i64, _solution: &Solution) {}
+}
+
+// Follows a search on behalf of an observer, counting the candidates explored in the size.
+pub(crate) struct Observed<'a> {
+    observer: &'a dyn SolverObserver,
+    size: AtomicUsize,
+    total: AtomicU64,
+    explored: AtomicU64,
+}
+
+impl Observed<'_> {
+    pub(crate) fn new(observer: &dyn SolverObserver) -> Observed<'_> {
+        Observed {
+            observer,
+            size: AtomicUsize::new(0),
+            total: AtomicU64::new(0),
+            explored: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Progress for Observed<'_> {
+    fn size_started(&self, size: usize, total: u64) {
+        self.size.store(size, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+        self.explored.store(0, Ordering::Relaxed);
+        self.observer.on_size_start(size, total);
+    }
+
+    fn explored(&self, candidates: u64) {
+        let explored = self.explored.fetch_add(candidates, Ordering::Relaxed) + candidates;
+        self.observer.on_progress(
+            self.size.load(Ordering::Relaxed),
+            explored,
+            self.total.load(Ordering::Relaxed),
+        );
+    }
+
+    fn discovered(&self, _values: usize) {}
+
+    fn solved(&self, value: i64, solution: &Solution) {
+        self.observer.on_value_found(value, solution);
+    }
+
+    fn size_finished(&self, _size: usize) {}
+}
+
+// Follows a search building a table of the values of the small sizes, which are only reported
+// as solved once the targets are composed from them.
+pub(crate) struct Unreported<'a>(pub(crate) &'a dyn Progress);
+
+impl Progress for Unreported<'_> {
+    fn size_started(&self, size: usize, total: u64) {
+        self.0.size_started(size, total);
+    }
+
+    fn explored(&self, candidates: u64) {
+        self.0.explored(candidates);
+    }
+
+    fn discovered(&self, values: usize) {
+        self.0.discovered(values);
+    }
+
+    fn solved(&self, _value: i64, _solution: &Solution) {}
+
+    fn size_finished(&self, size: usize) {
+        self.0.size_finished(size);
+    }
+}
+
+// Reports, in increasing order of value, the solutions not reported yet that no size after
+// `size` can make cheaper, or all of them once the search is over and `size` is None. The
+// solutions of every size only change until then.
+pub(crate) fn report_solved(
+    config: &SolverConfig,
+    solutions: &HashMap<i64, Solution>,
+    size: Option<usize>,
+    reported: &mut HashSet<i64>,
+    progress: &dyn Progress,
+) {
+    if config.all_sizes && size.is_some() {
+        return;
+    }
+    let minimum_cost = size.map_or(u64::MAX, |size| {
+        config
+            .objective
+            .minimum_cost(size + 1, &config.operations, &config.numbers)
+    });
+    let mut solved: Vec<(&i64, &Solution)> = solutions
+        .iter()
+        .filter(|(value, solution)| solution.cost <= minimum_cost && !reported.contains(value))
+        .collect();
+    solved.sort_by_key(|(value, _)| **value);
+    for (value, solution) in solved {
+        reported.insert(*value);
+        progress.solved(*value, solution);
+    }
+}
@@ -0,0 +1,117 @@
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+use std::io::{BufWriter, Write};
+use tracing::warn;
+
+use crate::{
+    exit_unsolved,
+    failure::{fail, Failure},
+    QueryArgs,
+};
+
+// The values from `from` to `to` included, given as `<from>..<to>`.
+fn range(range: &str) -> (i64, i64) {
+    match range
+        .split_once("..")
+        .and_then(|(from, to)| Some((from.trim().parse().ok()?, to.trim().parse().ok()?)))
+    {
+        Some((from, to)) if from <= to => (from, to),
+        _ => fail(
+            Failure::Arguments,
+            format!("range must be <from>..<to> with from <= to, was {}", range),
+        ),
+    }
+}
+
+// Prints the solutions of the values from a database exported with `--export`, as the text
+// format prints them, without searching again. Values out of the database are printed as
+// unsolved, counted on standard error, and exit as the unsolved targets of `solve` do.
+pub(crate) fn run(args: &QueryArgs) {
+    let mut values = args.values.clone();
+    if let Some((from, to)) = args.range.as_deref().map(range) {
+        values.extend(from..=to);
+    }
+    let database =
+        match Connection::open_with_flags(&args.database, OpenFlags::SQLITE_OPEN_READ_ONLY) {
+            Ok(database) => database,
+            Err(e) => fail(
+                Failure::Io,
+                format!("unable to read {}: {}", args.database.display(), e),
+            ),
+        };
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let result = print(&mut out, &database, &values).and_then(|missing| {
+        out.flush().map_err(|e| e.to_string())?;
+        Ok(missing)
+    });
+    match result {
+        Ok(0) => {}
+        Ok(missing) => {
+            match missing {
+                1 => warn!("1 value is not in the database"),
+                missing => warn!("{} values are not in the database", missing),
+            }
+            exit_unsolved(true);
+        }
+        Err(e) => fail(
+            Failure::Io,
+            format!("unable to query {}: {}", args.database.display(), e),
+        ),
+    }
+}
+
+// Prints the solutions of the values, returning how many are not in the database.
+fn print(out: &mut dyn Write, database: &Connection, values: &[i64]) -> Result<usize, String> {
+    let sql = |e: rusqlite::Error| e.to_string();
+    let weighted = database
+        .query_row(
+            "SELECT value FROM metadata WHERE key = 'weighted'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(sql)?
+        .is_some_and(|weighted| weighted == "true");
+    let mut solution = database
+        .prepare("SELECT size, cost, difficulty, minimal FROM solutions WHERE value = ?")
+        .map_err(sql)?;
+    let mut alternatives = database
+        .prepare("SELECT expression FROM alternatives WHERE value = ? ORDER BY position")
+        .map_err(sql)?;
+    let mut missing = 0;
+    for v in values {
+        let row: Option<[Option<i64>; 4]> = solution
+            .query_row([v], |row| {
+                Ok([row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?])
+            })
+            .optional()
+            .map_err(sql)?;
+        let Some([size, cost, difficulty, minimal]) = row else {
+            missing += 1;
+            writeln!(out, "{} -> None", v).map_err(|e| e.to_string())?;
+            continue;
+        };
+        let Some(size) = size else {
+            writeln!(out, "{} -> None", v).map_err(|e| e.to_string())?;
+            continue;
+        };
+        let mut size = size.to_string();
+        if let (true, Some(cost)) = (weighted, cost) {
+            size.push_str(&format!(", cost {}", cost));
+        }
+        if let Some(difficulty) = difficulty {
+            size.push_str(&format!(", difficulty {}", difficulty));
+        }
+        match minimal {
+            Some(0) => size.push_str(", not minimal"),
+            Some(_) => size.push_str(", minimal"),
+            None => {}
+        }
+        let expressions: Vec<String> = alternatives
+            .query_map([v], |row| row.get(0))
+            .and_then(|rows| rows.collect())
+            .map_err(sql)?;
+        writeln!(out, "{} -> ({}) {:?}", v, size, expressions).map_err(|e| e.to_string())?;
+    }
+    Ok(missing)
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use crate::{Expression, Operation, UnaryOperation};
+
+// The items per second delivered by the extractors and processed by the machines of each
+// operation. A machine consumes an item of every operand for each item it outputs, so an
+// expression delivers items at the rate of the slowest machine or extractor of its tree.
+// Operations without a rate process items as fast as the extractors deliver them.
+#[derive(Clone, Debug)]
+pub struct Rates {
+    extractor: f64,
+    rates: HashMap<Operation, f64>,
+    unary_rates: HashMap<UnaryOperation, f64>,
+}
+
+impl Rates {
+    pub fn new(
+        extractor: f64,
+        rates: HashMap<Operation, f64>,
+        unary_rates: HashMap<UnaryOperation, f64>,
+    ) -> Rates {
+        Rates {
+            extractor,
+            rates,
+            unary_rates,
+        }
+    }
+
+    pub fn extractor(&self) -> f64 {
+        self.extractor
+    }
+
+    pub fn rate(&self, operation: Operation) -> f64 {
+        self.rates
+            .get(&operation)
+            .copied()
+            .unwrap_or(self.extractor)
+    }
+
+    pub fn unary_rate(&self, operation: UnaryOperation) -> f64 {
+        self.unary_rates
+            .get(&operation)
+            .copied()
+            .unwrap_or(self.extractor)
+    }
+
+    // The items per second processed by the machine, or delivered by the extractor, at the root
+    // of the expression.
+    pub fn capacity(&self, expression: &Expression) -> f64 {
+        match expression {
+            Expression::Number(_) => self.extractor,
+            Expression::Binary(operation, _, _) => self.rate(*operation),
+            Expression::Unary(operation, _) => self.unary_rate(*operation),
+        }
+    }
+
+    // The branches to build several copies of to raise the throughput of the expression: the
+    // largest subexpressions rooted at a machine or extractor as slow as the throughput, as the
+    // copies of a branch multiply the rates of all its machines. Returned with the throughput
+    // the rest of the expression allows once the branches are fast enough, or None if the
+    // branch is the whole expression.
+    pub fn bottlenecks<'a>(
+        &self,
+        expression: &'a Expression,
+    ) -> (Vec<&'a Expression>, Option<f64>) {
+        let throughput = self.throughput(expression);
+        let mut branches = vec![];
+        self.collect_bottlenecks(expression, throughput, &mut branches);
+        if branches
+            .iter()
+            .any(|branch| std::ptr::eq(*branch, expression))
+        {
+            return (branches, None);
+        }
+        let limit = self.throughput_without(expression, &branches);
+        (branches, Some(limit))
+    }
+
+    fn collect_bottlenecks<'a>(
+        &self,
+        expression: &'a Expression,
+        throughput: f64,
+        branches: &mut Vec<&'a Expression>,
+    ) {
+        if self.capacity(expression) == throughput {
+            branches.push(expression);
+            return;
+        }
+        let operands: Vec<&Expression> = match expression {
+            Expression::Number(_) => vec![],
+            Expression::Binary(_, left, right) => vec![left, right],
+            Expression::Unary(_, operand) => vec![operand],
+        };
+        for operand in operands {
+            if self.throughput(operand) == throughput {
+                self.collect_bottlenecks(operand, throughput, branches);
+            }
+        }
+    }
+
+    // The throughput of the expression if the branches delivered as many items as needed.
+    fn throughput_without(&self, expression: &Expression, branches: &[&Expression]) -> f64 {
+        if branches
+            .iter()
+            .any(|branch| std::ptr::eq(*branch, expression))
+        {
+            return f64::INFINITY;
+        }
+        match expression {
+            Expression::Number(_) => self.extractor,
+            Expression::Binary(_, left, right) => self
+                .capacity(expression)
+                .min(self.throughput_without(left, branches))
+                .min(self.throughput_without(right, branches)),
+            Expression::Unary(_, operand) => self
+                .capacity(expression)
+                .min(self.throughput_without(operand, branches)),
+        }
+    }
+
+    // The items per second the expression delivers, with an extractor for every leaf.
+    pub fn throughput(&self, expression: &Expression) -> f64 {
+        match expression {
+            Expression::Number(_) => self.extractor,
+            Expression::Binary(_, left, right) => self
+                .capacity(expression)
+                .min(self.throughput(left))
+                .min(self.throughput(right)),
+            Expression::Unary(_, operand) => {
+                self.capacity(expression).min(self.throughput(operand))
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use beltmatic_calc::MinimalSizes;
+use serde::Serialize;
+use std::io::{BufWriter, Write};
+
+use crate::{
+    configure_threads,
+    failure::{fail, Failure},
+    solver_config, write_atomically, EnumerateArgs, Format,
+};
+
+#[derive(Serialize)]
+struct JsonReachability {
+    max_number: Option<i64>,
+    numbers: Vec<i64>,
+    max_size: usize,
+    operations: Vec<String>,
+    from: i64,
+    to: i64,
+    sizes: Vec<JsonSize>,
+}
+
+#[derive(Serialize)]
+struct JsonSize {
+    size: usize,
+    // The values of the range first reachable with the size, and those reachable with up to it.
+    new: usize,
+    reachable: usize,
+}
+
+// Counts, for every size, the values of the printed range reachable with up to that many
+// numbers, from the sets of values reachable with each size: no expression is built, which
+// makes it faster and lighter than a search when only feasibility matters.
+pub(crate) fn run(args: &EnumerateArgs) {
+    if matches!(args.output.format, Format::Dot | Format::Latex) {
+        fail(
+            Failure::Arguments,
+            "count_only is only supported by the text, json, csv and tsv formats",
+        );
+    }
+    configure_threads(args.search.threads);
+    let config = solver_config(&args.search);
+    let sizes = MinimalSizes::new(&config, config.max_size());
+    let from = args.print_from.unwrap_or(1);
+    let to = args
+        .print_to
+        .unwrap_or_else(|| sizes.largest().unwrap_or(1).max(1));
+    let counts = sizes.counts(from..=to);
+    let mut reachable = 0;
+    let sizes: Vec<JsonSize> = (1..=config.max_size())
+        .map(|size| {
+            let new = counts.get(&size).copied().unwrap_or(0);
+            reachable += new;
+            JsonSize {
+                size,
+                new,
+                reachable,
+            }
+        })
+        .collect();
+    let total = (to - from + 1).max(0);
+    let print = |out: &mut dyn Write| match args.output.format {
+        Format::Json => {
+            let report = JsonReachability {
+                max_number: args.search.max_number,
+                numbers: config.numbers().to_vec(),
+                max_size: config.max_size(),
+                operations: config.operations().symbols(),
+                from,
+                to,
+                sizes,
+            };
+            serde_json::to_writer(&mut *out, &report)?;
+            writeln!(out)
+        }
+        Format::Csv | Format::Tsv => {
+            let separator = if matches!(args.output.format, Format::Csv) {
+                ','
+            } else {
+                '\t'
+            };
+            writeln!(out, "size{0}new{0}reachable", separator)?;
+            for size in sizes.iter() {
+                writeln!(
+                    out,
+                    "{1}{0}{2}{0}{3}",
+                    separator, size.size, size.new, size.reachable
+                )?;
+            }
+            Ok(())
+        }
+        _ => {
+            for size in sizes.iter() {
+                writeln!(
+                    out,
+                    "size {}: {} new, {} of {}-{} reachable ({:.1}%)",
+                    size.size,
+                    size.new,
+                    size.reachable,
+                    from,
+                    to,
+                    100.0 * size.reachable as f64 / total.max(1) as f64
+                )?;
+            }
+            Ok(())
+        }
+    };
+    let result = match &args.output.output {
+        Some(path) => write_atomically(path, print),
+        None => {
+            let stdout = std::io::stdout();
+            let mut out = BufWriter::new(stdout.lock());
+            print(&mut out).and_then(|_| out.flush())
+        }
+    };
+    if let Err(e) = result {
+        fail(Failure::Io, format!("unable to write results: {}", e));
+    }
+}
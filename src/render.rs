@@ -0,0 +1,334 @@
+use std::fmt::{self, Display, Write};
+
+use crate::{EvalError, Expression, Operation, Semantics, UnaryOperation};
+
+impl Expression {
+    // Writes the expression tree as DOT node and edge statements, naming the nodes `{prefix}{n}`.
+    // Numbers are extractors drawn as circles, operations are machines drawn as boxes labelled
+    // with their result, and the edges follow the belts from the operands to the machines.
+    pub fn write_dot(
+        &self,
+        out: &mut dyn Write,
+        prefix: &str,
+        semantics: Semantics,
+    ) -> fmt::Result {
+        self.write_dot_node(out, prefix, semantics, &mut 0)
+            .map(|_| ())
+    }
+
+    fn write_dot_node(
+        &self,
+        out: &mut dyn Write,
+        prefix: &str,
+        semantics: Semantics,
+        next: &mut usize,
+    ) -> Result<usize, fmt::Error> {
+        let id = *next;
+        *next += 1;
+        match self {
+            Expression::Number(value) => writeln!(
+                out,
+                "  {}{} [label=\"{}\", shape=circle];",
+                prefix, id, value
+            )?,
+            Expression::Binary(operation, left, right) => {
+                let label = match self.eval(semantics) {
+                    Ok(value) => format!("{}\\n{}", operation, value),
+                    Err(_) => operation.to_string(),
+                };
+                writeln!(out, "  {}{} [label=\"{}\", shape=box];", prefix, id, label)?;
+                for operand in [left, right] {
+                    let operand_id = operand.write_dot_node(out, prefix, semantics, next)?;
+                    writeln!(out, "  {}{} -> {}{};", prefix, operand_id, prefix, id)?;
+                }
+            }
+            Expression::Unary(operation, operand) => {
+                let label = match self.eval(semantics) {
+                    Ok(value) => format!("{}\\n{}", operation, value),
+                    Err(_) => operation.to_string(),
+                };
+                writeln!(out, "  {}{} [label=\"{}\", shape=box];", prefix, id, label)?;
+                let operand_id = operand.write_dot_node(out, prefix, semantics, next)?;
+                writeln!(out, "  {}{} -> {}{};", prefix, operand_id, prefix, id)?;
+            }
+        }
+        Ok(id)
+    }
+}
+
+impl Expression {
+    // Draws the expression as a tree, one node per line, labelling the operations with the
+    // value they produce:
+    //
+    // 97 (+)
+    // ├── 90 (*)
+    // │   ├── 10
+    // │   └── 9
+    // └── 7
+    pub fn tree(&self, semantics: Semantics) -> String {
+        let mut out = String::new();
+        self.write_tree(&mut out, "", "", semantics);
+        out
+    }
+
+    fn write_tree(&self, out: &mut String, first: &str, rest: &str, semantics: Semantics) {
+        match self {
+            Expression::Number(value) => out.push_str(&format!("{}{}\n", first, value)),
+            Expression::Unary(operation, operand) => {
+                match self.eval(semantics) {
+                    Ok(value) => out.push_str(&format!("{}{} ({})\n", first, value, operation)),
+                    Err(e) => out.push_str(&format!("{}{} ({})\n", first, e, operation)),
+                }
+                operand.write_tree(
+                    out,
+                    &format!("{}└── ", rest),
+                    &format!("{}    ", rest),
+                    semantics,
+                );
+            }
+            Expression::Binary(operation, left, right) => {
+                match self.eval(semantics) {
+                    Ok(value) => out.push_str(&format!("{}{} ({})\n", first, value, operation)),
+                    Err(e) => out.push_str(&format!("{}{} ({})\n", first, e, operation)),
+                }
+                left.write_tree(
+                    out,
+                    &format!("{}├── ", rest),
+                    &format!("{}│   ", rest),
+                    semantics,
+                );
+                right.write_tree(
+                    out,
+                    &format!("{}└── ", rest),
+                    &format!("{}    ", rest),
+                    semantics,
+                );
+            }
+        }
+    }
+}
+
+impl Expression {
+    // Reverse Polish notation, e.g. `10 9 * 7 +`: the operands of each operation are written
+    // before it, in the order the machines are chained.
+    pub fn rpn(&self) -> String {
+        match self {
+            Expression::Number(value) => value.to_string(),
+            Expression::Binary(operation, left, right) => {
+                format!("{} {} {}", left.rpn(), right.rpn(), operation)
+            }
+            Expression::Unary(operation, operand) => format!("{} {}", operand.rpn(), operation),
+        }
+    }
+
+    // Function calls named after the operations, e.g. `add(7, mul(9, 10))`, for tools parsing
+    // calls but not operators.
+    pub fn call(&self) -> String {
+        match self {
+            Expression::Number(value) => value.to_string(),
+            Expression::Binary(operation, left, right) => {
+                format!("{}({}, {})", operation.name(), left.call(), right.call())
+            }
+            Expression::Unary(operation, operand) => format!("{}({})", operation, operand.call()),
+        }
+    }
+
+    // S-expressions, e.g. `(+ 7 (* 9 10))`, the operation first in every list.
+    pub fn sexpr(&self) -> String {
+        match self {
+            Expression::Number(value) => value.to_string(),
+            Expression::Binary(operation, left, right) => {
+                format!("({} {} {})", operation, left.sexpr(), right.sexpr())
+            }
+            Expression::Unary(operation, operand) => format!("({} {})", operation, operand.sexpr()),
+        }
+    }
+
+    // The infix notation with the symbols of mathematics, e.g. `((10×9)+7)` or `(10³)`:
+    // multiplications and divisions are written `×` and `÷`, and exponents that are numbers
+    // superscripts.
+    pub fn pretty(&self) -> String {
+        match self {
+            Expression::Number(value) => value.to_string(),
+            Expression::Binary(Operation::EXP, base, exponent) => match exponent.as_ref() {
+                Expression::Number(exponent) if *exponent >= 0 => {
+                    format!("({}{})", base.pretty(), superscript(*exponent))
+                }
+                _ => format!("({}^{})", base.pretty(), exponent.pretty()),
+            },
+            Expression::Binary(operation, left, right) => {
+                format!(
+                    "({}{}{})",
+                    left.pretty(),
+                    symbol(*operation),
+                    right.pretty()
+                )
+            }
+            Expression::Unary(operation, operand) => {
+                format!("({}{})", operation.prefix(), operand.pretty())
+            }
+        }
+    }
+
+    // LaTeX math, e.g. `7 + 9 \cdot 10`, for typesetting: divisions are fractions, exponents
+    // superscripts, and only the parentheses the precedence of the operations requires are
+    // written, the right operand being parenthesized when of the same precedence.
+    pub fn latex(&self) -> String {
+        self.latex_with_precedence().0
+    }
+
+    // The LaTeX of the expression, and how tightly it binds: operands binding less tightly than
+    // the operation using them are parenthesized.
+    fn latex_with_precedence(&self) -> (String, u8) {
+        let parenthesize = |operand: &Expression, at_least: u8| {
+            let (latex, precedence) = operand.latex_with_precedence();
+            if precedence < at_least {
+                format!("\\left({}\\right)", latex)
+            } else {
+                latex
+            }
+        };
+        match self {
+            Expression::Number(value) if *value < 0 => (value.to_string(), 1),
+            Expression::Number(value) => (value.to_string(), 5),
+            Expression::Binary(Operation::DIV, left, right) => (
+                format!("\\frac{{{}}}{{{}}}", left.latex(), right.latex()),
+                4,
+            ),
+            Expression::Binary(Operation::EXP, base, exponent) => (
+                format!("{}^{{{}}}", parenthesize(base, 5), exponent.latex()),
+                3,
+            ),
+            Expression::Binary(operation, left, right) => {
+                let (symbol, precedence) = match operation {
+                    Operation::ADD => ("+", 1),
+                    Operation::SUB => ("-", 1),
+                    Operation::MULT => ("\\cdot", 2),
+                    _ => ("\\mathbin{\\|}", 4),
+                };
+                (
+                    format!(
+                        "{} {} {}",
+                        parenthesize(left, precedence),
+                        symbol,
+                        parenthesize(right, precedence + 1)
+                    ),
+                    precedence,
+                )
+            }
+            Expression::Unary(UnaryOperation::NEG, operand) => {
+                (format!("-{}", parenthesize(operand, 3)), 1)
+            }
+        }
+    }
+}
+
+// The symbol of the operation in mathematics.
+fn symbol(operation: Operation) -> String {
+    match operation {
+        Operation::MULT => "×".to_string(),
+        Operation::DIV => "÷".to_string(),
+        operation => operation.to_string(),
+    }
+}
+
+fn superscript(exponent: i64) -> String {
+    const SUPERSCRIPTS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+    exponent
+        .to_string()
+        .bytes()
+        .map(|digit| SUPERSCRIPTS[(digit - b'0') as usize])
+        .collect()
+}
+
+// A machine of a build plan, combining two values, or transforming one, into `result`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Step {
+    Binary {
+        operation: Operation,
+        left: i64,
+        right: i64,
+        result: i64,
+    },
+    Unary {
+        operation: UnaryOperation,
+        operand: i64,
+        result: i64,
+    },
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Binary {
+                operation,
+                left,
+                right,
+                result,
+            } => write!(f, "{} {} {} = {}", left, operation, right, result),
+            Step::Unary {
+                operation,
+                operand,
+                result,
+            } => write!(f, "{} {} = {}", operation, operand, result),
+        }
+    }
+}
+
+impl Step {
+    // The step with the symbols of mathematics, e.g. `10³ = 1000`, as in `Expression::pretty`.
+    pub fn pretty(&self) -> String {
+        match self {
+            Step::Binary {
+                operation: Operation::EXP,
+                left,
+                right,
+                result,
+            } if *right >= 0 => format!("{}{} = {}", left, superscript(*right), result),
+            Step::Binary {
+                operation,
+                left,
+                right,
+                result,
+            } => format!("{} {} {} = {}", left, symbol(*operation), right, result),
+            Step::Unary { .. } => self.to_string(),
+        }
+    }
+}
+
+impl Expression {
+    // The operations of the expression in the order they can be built, operands first.
+    pub fn steps(&self, semantics: Semantics) -> Result<Vec<Step>, EvalError> {
+        let mut steps = vec![];
+        self.push_steps(&mut steps, semantics)?;
+        Ok(steps)
+    }
+
+    fn push_steps(&self, steps: &mut Vec<Step>, semantics: Semantics) -> Result<i64, EvalError> {
+        match self {
+            Expression::Number(value) => Ok(*value),
+            Expression::Binary(operation, left, right) => {
+                let left = left.push_steps(steps, semantics)?;
+                let right = right.push_steps(steps, semantics)?;
+                let result = operation.apply(left, right, semantics)?;
+                steps.push(Step::Binary {
+                    operation: *operation,
+                    left,
+                    right,
+                    result,
+                });
+                Ok(result)
+            }
+            Expression::Unary(operation, operand) => {
+                let operand = operand.push_steps(steps, semantics)?;
+                let result = operation.apply(operand, semantics)?;
+                steps.push(Step::Unary {
+                    operation: *operation,
+                    operand,
+                    result,
+                });
+                Ok(result)
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use beltmatic_calc::{Solutions, Solver};
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use crate::{
+    build, configure_threads,
+    failure::{fail, Failure},
+    print_text, progress, report_covered, solver_builder, warn_truncated, Annotations, SearchArgs,
+    Syntax,
+};
+
+const HELP: &str = "\
+<value> [<value> ...]  print the minimal expressions of the values
+ops <operations>       switch to a CSV list of operations, e.g. ops +,*
+help                   print this message
+quit                   exit";
+
+pub(crate) fn run(args: &SearchArgs) {
+    configure_threads(args.threads);
+    let progress = progress(args);
+    let mut builder = solver_builder(args);
+    let mut config = build(builder.clone());
+    let show_cost = config.objective().is_weighted();
+    // The solutions of every set of operations searched are kept, so that switching back to
+    // them does not search again.
+    let mut searched: HashMap<Vec<String>, Solutions> = HashMap::new();
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        let operations = config.operations().symbols();
+        let prompt = operations.join(",");
+        let solutions = searched.entry(operations).or_insert_with(|| {
+            let solutions = Solver::new(config.clone()).solve_with_progress(progress.as_ref());
+            warn_truncated(&solutions);
+            report_covered(args, &config, &solutions);
+            solutions
+        });
+        print!("[{}]> ", prompt);
+        let _ = io::stdout().flush();
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+        let mut words = line.split_whitespace();
+        match words.next() {
+            None => {}
+            Some("quit" | "exit") => break,
+            Some("help") => println!("{}", HELP),
+            Some("ops") => {
+                let operations: Vec<String> = words
+                    .next()
+                    .unwrap_or_default()
+                    .split(",")
+                    .map(|s| s.to_string())
+                    .collect();
+                match builder.clone().operations(&operations).build() {
+                    Ok(switched) => {
+                        builder = builder.operations(&operations);
+                        config = switched;
+                    }
+                    Err(e) => println!("{}", e),
+                }
+            }
+            Some(_) => {
+                let values: Result<Vec<i64>, _> =
+                    line.split_whitespace().map(|s| s.parse()).collect();
+                match values {
+                    Ok(values) => {
+                        let stdout = io::stdout();
+                        let mut out = stdout.lock();
+                        for v in values {
+                            if let Err(e) = print_text(
+                                &mut out,
+                                solutions,
+                                v..v.saturating_add(1),
+                                show_cost,
+                                Syntax::Infix,
+                                config.semantics(),
+                                &Annotations::default(),
+                            ) {
+                                fail(Failure::Io, format!("unable to write results: {}", e));
+                            }
+                        }
+                    }
+                    Err(_) => {
+                        println!("unrecognised command: {}, type help for the commands", line)
+                    }
+                }
+            }
+        }
+    }
+}
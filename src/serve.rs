@@ -0,0 +1,706 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        MatchedPath, Path, Query, Request, State,
+    },
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use beltmatic_calc::{
+    CancellationToken, Progress, Semantics, Solution, Solutions, Solver, SolverConfigBuilder,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    Semaphore,
+};
+use tracing::{info, warn};
+
+use crate::{
+    build, configure_threads,
+    failure::{fail, Failure},
+    metrics::Metrics,
+    progress, report_covered, solver_builder,
+    stats::Statistics,
+    truncated_by, warn_truncated, JsonValue, ServeArgs, Syntax,
+};
+
+// The events of the jobs kept for the subscribers lagging behind, past which they miss some.
+const EVENTS: usize = 1024;
+
+struct Dictionary {
+    solutions: Solutions,
+    max_size: usize,
+    semantics: Semantics,
+    statistics: Statistics,
+    // The configuration of the search, from which the jobs search their own targets.
+    builder: SolverConfigBuilder,
+    jobs: Mutex<Jobs>,
+    // The largest size a job can search.
+    max_job_size: usize,
+    // The jobs allowed to run at the same time, and a permit for each of them.
+    max_jobs: usize,
+    running: Arc<Semaphore>,
+    // How long the jobs done are kept to be polled.
+    job_ttl: Duration,
+    // The events of the jobs, sent to every WebSocket subscribed.
+    events: broadcast::Sender<Arc<Event>>,
+    metrics: Metrics,
+}
+
+type Failed = (StatusCode, String);
+
+// The jobs submitted, by id, and the id of the next one.
+#[derive(Default)]
+struct Jobs {
+    jobs: BTreeMap<usize, JsonJob>,
+    next: usize,
+}
+
+impl Jobs {
+    // Forgets the jobs done for longer than `ttl`, so that the jobs kept stay bounded.
+    fn expire(&mut self, ttl: Duration) {
+        self.jobs
+            .retain(|_, job| job.done.is_none_or(|done| done.elapsed() < ttl));
+    }
+}
+
+// The size requested, at most `limit`, which it defaults to.
+fn at_most(max_size: Option<usize>, limit: usize) -> Result<usize, Failed> {
+    let max_size = max_size.unwrap_or(limit);
+    if max_size > limit {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("max_size must be at most {}, was {}", limit, max_size),
+        ));
+    }
+    Ok(max_size)
+}
+
+impl Dictionary {
+    // The largest size of the solutions returned, at most the size searched.
+    fn max_size(&self, max_size: Option<usize>) -> Result<usize, Failed> {
+        at_most(max_size, self.max_size)
+    }
+
+    fn jobs(&self) -> std::sync::MutexGuard<'_, Jobs> {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.expire(self.job_ttl);
+        jobs
+    }
+
+    fn value(&self, target: i64, max_size: usize) -> JsonValue {
+        let solution = self
+            .solutions
+            .get(target)
+            .filter(|solution| solution.size <= max_size);
+        self.metrics.lookup(solution.is_some());
+        JsonValue::new(target, solution, Syntax::Infix, self.semantics)
+    }
+}
+
+// Counts the requests answered by every route.
+async fn count_requests(
+    State(dictionary): State<Arc<Dictionary>>,
+    route: MatchedPath,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    dictionary
+        .metrics
+        .request(route.as_str(), response.status().as_u16());
+    response
+}
+
+async fn metrics(State(dictionary): State<Arc<Dictionary>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        dictionary.metrics.render(),
+    )
+}
+
+#[derive(Deserialize)]
+struct SolveQuery {
+    target: i64,
+    max_size: Option<usize>,
+}
+
+async fn solve(
+    State(dictionary): State<Arc<Dictionary>>,
+    Query(query): Query<SolveQuery>,
+) -> Result<Json<JsonValue>, Failed> {
+    let max_size = dictionary.max_size(query.max_size)?;
+    Ok(Json(dictionary.value(query.target, max_size)))
+}
+
+#[derive(Deserialize)]
+struct Batch {
+    targets: Vec<i64>,
+    max_size: Option<usize>,
+}
+
+async fn solve_batch(
+    State(dictionary): State<Arc<Dictionary>>,
+    Json(batch): Json<Batch>,
+) -> Result<Json<Vec<JsonValue>>, Failed> {
+    let max_size = dictionary.max_size(batch.max_size)?;
+    Ok(Json(
+        batch
+            .targets
+            .iter()
+            .map(|target| dictionary.value(*target, max_size))
+            .collect(),
+    ))
+}
+
+async fn stats(State(dictionary): State<Arc<Dictionary>>) -> Json<Value> {
+    let mut statistics = json!(dictionary.statistics);
+    statistics["max_size"] = json!(dictionary.max_size);
+    Json(statistics)
+}
+
+// A search of targets out of the dictionary, e.g. needing more numbers than it was searched
+// with, run in the background until its values are polled.
+#[derive(Clone, Serialize)]
+struct JsonJob {
+    id: usize,
+    status: &'static str,
+    targets: Vec<i64>,
+    max_size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    values: Option<Vec<JsonValue>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    truncated_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    truncated_by: Option<&'static str>,
+    #[serde(skip)]
+    cancellation: CancellationToken,
+    // When the job was done, after which it expires.
+    #[serde(skip)]
+    done: Option<Instant>,
+}
+
+async fn submit_job(
+    State(dictionary): State<Arc<Dictionary>>,
+    Json(batch): Json<Batch>,
+) -> Result<(StatusCode, Json<JsonJob>), Failed> {
+    if batch.targets.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "targets must not be empty".to_string(),
+        ));
+    }
+    let max_size = at_most(batch.max_size, dictionary.max_job_size)?;
+    let cancellation = CancellationToken::new();
+    let config = dictionary
+        .builder
+        .clone()
+        .targets(batch.targets.clone())
+        .max_size(max_size)
+        .cover(None)
+        .cancellation(Some(cancellation.clone()))
+        .build()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let Ok(permit) = dictionary.running.clone().try_acquire_owned() else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!(
+                "too many jobs running, at most {} at a time",
+                dictionary.max_jobs
+            ),
+        ));
+    };
+    let job = {
+        let mut jobs = dictionary.jobs();
+        let job = JsonJob {
+            id: jobs.next,
+            status: "running",
+            targets: batch.targets,
+            max_size,
+            values: None,
+            truncated_size: None,
+            truncated_by: None,
+            cancellation,
+            done: None,
+        };
+        jobs.next += 1;
+        jobs.jobs.insert(job.id, job.clone());
+        job
+    };
+    info!("job {} started for {:?}", job.id, job.targets);
+    dictionary.metrics.job_started();
+    let id = job.id;
+    dictionary.publish(Event::Started {
+        job: id,
+        targets: job.targets.clone(),
+        max_size: job.max_size,
+    });
+    let targets = job.targets.clone();
+    tokio::task::spawn_blocking(move || {
+        let solver = Solver::new(config);
+        let solutions = solver.solve_with_progress(&JobProgress::new(id, targets, &dictionary));
+        drop(permit);
+        let mut jobs = dictionary.jobs.lock().unwrap();
+        let Some(job) = jobs.jobs.get_mut(&id) else {
+            return;
+        };
+        job.values = Some(
+            job.targets
+                .iter()
+                .map(|target| {
+                    JsonValue::new(
+                        *target,
+                        solutions.get(*target),
+                        Syntax::Infix,
+                        dictionary.semantics,
+                    )
+                })
+                .collect(),
+        );
+        job.truncated_size = solutions.truncated();
+        job.truncated_by = truncated_by(&solutions);
+        job.status = "done";
+        job.done = Some(Instant::now());
+        info!("job {} done", id);
+        dictionary.metrics.job_done();
+        dictionary.publish(Event::Done { job: id });
+    });
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+async fn job(
+    State(dictionary): State<Arc<Dictionary>>,
+    Path(id): Path<usize>,
+) -> Result<Json<JsonJob>, Failed> {
+    match dictionary.jobs().jobs.get(&id) {
+        Some(job) => Ok(Json(job.clone())),
+        None => Err((StatusCode::NOT_FOUND, format!("no job {}", id))),
+    }
+}
+
+// Stops a running job, which is done with the values found so far, truncated by `cancelled`.
+async fn cancel_job(
+    State(dictionary): State<Arc<Dictionary>>,
+    Path(id): Path<usize>,
+) -> Result<(StatusCode, Json<JsonJob>), Failed> {
+    match dictionary.jobs().jobs.get(&id) {
+        Some(job) => {
+            if job.status == "running" {
+                info!("job {} cancelled", id);
+                job.cancellation.cancel();
+            }
+            Ok((StatusCode::ACCEPTED, Json(job.clone())))
+        }
+        None => Err((StatusCode::NOT_FOUND, format!("no job {}", id))),
+    }
+}
+
+// What happens to the jobs, streamed to the WebSockets as JSON objects telling the `event`.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event {
+    Started {
+        job: usize,
+        targets: Vec<i64>,
+        max_size: usize,
+    },
+    SizeStarted {
+        job: usize,
+        size: usize,
+        total: u64,
+    },
+    // Sent every time another percent of the candidates of a size is explored.
+    Progress {
+        job: usize,
+        size: usize,
+        explored: u64,
+        total: u64,
+        values: usize,
+    },
+    // A target whose minimal solution is known, before the job is done.
+    Solved {
+        job: usize,
+        #[serde(flatten)]
+        value: JsonValue,
+    },
+    SizeFinished {
+        job: usize,
+        size: usize,
+    },
+    // The job is over, and its values can be polled.
+    Done {
+        job: usize,
+    },
+}
+
+impl Event {
+    fn job(&self) -> usize {
+        match self {
+            Event::Started { job, .. }
+            | Event::SizeStarted { job, .. }
+            | Event::Progress { job, .. }
+            | Event::Solved { job, .. }
+            | Event::SizeFinished { job, .. }
+            | Event::Done { job } => *job,
+        }
+    }
+}
+
+impl Dictionary {
+    // Sends the event to the WebSockets subscribed, if any.
+    fn publish(&self, event: Event) {
+        let _ = self.events.send(Arc::new(event));
+    }
+}
+
+// Publishes the progress of the search of a job.
+struct JobProgress<'a> {
+    job: usize,
+    dictionary: &'a Dictionary,
+    targets: Vec<i64>,
+    size: AtomicUsize,
+    total: AtomicU64,
+    explored: AtomicU64,
+    values: AtomicUsize,
+    // The percent of the candidates of the size last published.
+    percent: AtomicU64,
+}
+
+impl JobProgress<'_> {
+    fn new(job: usize, targets: Vec<i64>, dictionary: &Dictionary) -> JobProgress<'_> {
+        JobProgress {
+            job,
+            dictionary,
+            targets,
+            size: AtomicUsize::new(0),
+            total: AtomicU64::new(0),
+            explored: AtomicU64::new(0),
+            values: AtomicUsize::new(0),
+            percent: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Progress for JobProgress<'_> {
+    fn size_started(&self, size: usize, total: u64) {
+        self.size.store(size, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+        self.explored.store(0, Ordering::Relaxed);
+        self.percent.store(0, Ordering::Relaxed);
+        self.dictionary.publish(Event::SizeStarted {
+            job: self.job,
+            size,
+            total,
+        });
+    }
+
+    fn explored(&self, candidates: u64) {
+        self.dictionary.metrics.explored(candidates);
+        let explored = self.explored.fetch_add(candidates, Ordering::Relaxed) + candidates;
+        let total = self.total.load(Ordering::Relaxed);
+        let percent = explored.saturating_mul(100) / total.max(1);
+        let last = self.percent.load(Ordering::Relaxed);
+        if percent > last
+            && self
+                .percent
+                .compare_exchange(last, percent, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.dictionary.publish(Event::Progress {
+                job: self.job,
+                size: self.size.load(Ordering::Relaxed),
+                explored,
+                total,
+                values: self.values.load(Ordering::Relaxed),
+            });
+        }
+    }
+
+    fn discovered(&self, values: usize) {
+        self.values.store(values, Ordering::Relaxed);
+    }
+
+    fn solved(&self, value: i64, solution: &Solution) {
+        if self.targets.contains(&value) {
+            self.dictionary.publish(Event::Solved {
+                job: self.job,
+                value: JsonValue::new(
+                    value,
+                    Some(solution),
+                    Syntax::Infix,
+                    self.dictionary.semantics,
+                ),
+            });
+        }
+    }
+
+    fn size_finished(&self, size: usize) {
+        self.dictionary.publish(Event::SizeFinished {
+            job: self.job,
+            size,
+        });
+    }
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    job: Option<usize>,
+}
+
+// Upgrades the connection to a WebSocket sending the events of the jobs, or of the given job,
+// as text messages from then on.
+async fn events(
+    State(dictionary): State<Arc<Dictionary>>,
+    Query(query): Query<EventsQuery>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let receiver = dictionary.events.subscribe();
+    upgrade.on_upgrade(move |socket| send_events(socket, receiver, query.job))
+}
+
+// Sends the events until the client closes the WebSocket. Its messages are read meanwhile, so
+// that its pings are answered.
+async fn send_events(
+    mut socket: WebSocket,
+    mut receiver: broadcast::Receiver<Arc<Event>>,
+    job: Option<usize>,
+) {
+    loop {
+        let event = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+            event = receiver.recv() => event,
+        };
+        let event = match event {
+            Ok(event) => event,
+            Err(RecvError::Lagged(missed)) => {
+                warn!("a WebSocket missed {} events", missed);
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+        if job.is_some_and(|job| job != event.job()) {
+            continue;
+        }
+        let Ok(text) = serde_json::to_string(event.as_ref()) else {
+            continue;
+        };
+        if socket.send(Message::Text(text)).await.is_err() {
+            return;
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+async fn openapi() -> Json<Value> {
+    let value = json!({
+        "type": "object",
+        "properties": {
+            "value": {"type": "integer"},
+            "size": {"type": "integer", "nullable": true},
+            "cost": {"type": "integer", "nullable": true},
+            "count": {"type": "integer"},
+            "alternatives": {"type": "array", "items": {"type": "string"}},
+        },
+    });
+    let batch = json!({
+        "type": "object",
+        "required": ["targets"],
+        "properties": {
+            "targets": {"type": "array", "items": {"type": "integer"}},
+            "max_size": {"type": "integer"},
+        },
+    });
+    let job = json!({
+        "type": "object",
+        "properties": {
+            "id": {"type": "integer"},
+            "status": {"type": "string", "enum": ["running", "done"]},
+            "targets": {"type": "array", "items": {"type": "integer"}},
+            "max_size": {"type": "integer"},
+            "values": {"type": "array", "items": {"$ref": "#/components/schemas/Value"}},
+            "truncated_size": {"type": "integer"},
+            "truncated_by": {"type": "string", "enum": ["timeout", "memory", "cancelled"]},
+        },
+    });
+    let statistics = json!({
+        "type": "object",
+        "properties": {
+            "values": {"type": "integer"},
+            "largest": {"type": "integer"},
+            "covered": {"type": "integer"},
+            "sizes": {"type": "object", "additionalProperties": {"type": "integer"}},
+            "uses": {"type": "object", "additionalProperties": {"type": "integer"}},
+            "overflows": {"type": "integer"},
+            "max_size": {"type": "integer"},
+        },
+    });
+    let reference = |schema: &str| json!({"$ref": format!("#/components/schemas/{}", schema)});
+    let content = |schema: Value| json!({"application/json": {"schema": schema}});
+    let response = |description: &str, schema: Value| json!({"description": description, "content": content(schema)});
+    let bad_request = json!({"description": "Invalid request, with the reason as text"});
+    Json(json!({
+        "openapi": "3.0.3",
+        "info": {"title": "beltmatic-calc", "version": env!("CARGO_PKG_VERSION")},
+        "paths": {
+            "/solve": {"get": {
+                "summary": "The minimal solutions of a target searched at startup",
+                "parameters": [
+                    {"name": "target", "in": "query", "required": true,
+                        "schema": {"type": "integer"}},
+                    {"name": "max_size", "in": "query", "schema": {"type": "integer"}},
+                ],
+                "responses": {
+                    "200": response("The solutions of the target", reference("Value")),
+                    "400": bad_request,
+                },
+            }},
+            "/solve/batch": {"post": {
+                "summary": "The minimal solutions of several targets searched at startup",
+                "requestBody": {"required": true, "content": content(reference("Batch"))},
+                "responses": {
+                    "200": response(
+                        "The solutions of the targets, in order",
+                        json!({"type": "array", "items": reference("Value")}),
+                    ),
+                    "400": bad_request,
+                },
+            }},
+            "/stats": {"get": {
+                "summary": "Statistics about the values found at startup",
+                "responses": {"200": response("The statistics", reference("Statistics"))},
+            }},
+            "/jobs": {"post": {
+                "summary": "Searches targets in the background, up to the size allowed to jobs",
+                "requestBody": {"required": true, "content": content(reference("Batch"))},
+                "responses": {
+                    "202": response("The job started", reference("Job")),
+                    "400": bad_request,
+                    "503": {"description": "As many jobs as allowed are running"},
+                },
+            }},
+            "/jobs/{id}": {"get": {
+                "summary": "The status of a job, with the solutions once done until it expires",
+                "parameters": [
+                    {"name": "id", "in": "path", "required": true,
+                        "schema": {"type": "integer"}},
+                ],
+                "responses": {
+                    "200": response("The job", reference("Job")),
+                    "404": {"description": "No such job, or expired"},
+                },
+            }, "delete": {
+                "summary": "Cancels a job, done with the solutions found so far",
+                "parameters": [
+                    {"name": "id", "in": "path", "required": true,
+                        "schema": {"type": "integer"}},
+                ],
+                "responses": {
+                    "202": response("The job cancelled", reference("Job")),
+                    "404": {"description": "No such job, or expired"},
+                },
+            }},
+            "/ws": {"get": {
+                "summary": "A WebSocket streaming the events of the jobs as JSON text messages",
+                "parameters": [
+                    {"name": "job", "in": "query", "schema": {"type": "integer"}},
+                ],
+                "responses": {
+                    "101": {"description": "Events tagged by their `event`: started, \
+                        size_started, progress, solved, size_finished and done"},
+                    "400": bad_request,
+                },
+            }},
+            "/metrics": {"get": {
+                "summary": "The counters of the server in the Prometheus text format",
+                "responses": {"200": {"description": "The metrics", "content": {
+                    "text/plain": {"schema": {"type": "string"}},
+                }}},
+            }},
+            "/openapi.json": {"get": {
+                "summary": "This document",
+                "responses": {"200": {"description": "The OpenAPI document"}},
+            }},
+        },
+        "components": {"schemas": {
+            "Value": value,
+            "Batch": batch,
+            "Job": job,
+            "Statistics": statistics,
+        }},
+    }))
+}
+
+pub(crate) fn run(args: &ServeArgs) {
+    if args.max_jobs == 0 {
+        fail(Failure::Arguments, "max_jobs must be > 0");
+    }
+    configure_threads(args.search.threads);
+    let builder = solver_builder(&args.search);
+    let solver = Solver::new(build(builder.clone()));
+    let semantics = solver.config().semantics();
+    let solutions = solver.solve_with_progress(progress(&args.search).as_ref());
+    warn_truncated(&solutions);
+    report_covered(&args.search, solver.config(), &solutions);
+    let max_size = args.search.max_size.unwrap_or(solutions.size_searched());
+    let dictionary = Arc::new(Dictionary {
+        max_size,
+        statistics: Statistics::new(solver.config(), &solutions),
+        solutions,
+        semantics,
+        builder,
+        jobs: Mutex::default(),
+        max_job_size: args.max_job_size.unwrap_or(max_size),
+        max_jobs: args.max_jobs,
+        running: Arc::new(Semaphore::new(args.max_jobs)),
+        job_ttl: Duration::from_secs(args.job_ttl),
+        events: broadcast::channel(EVENTS).0,
+        metrics: Metrics::default(),
+    });
+    let app = Router::new()
+        .route("/solve", get(solve))
+        .route("/solve/batch", post(solve_batch))
+        .route("/stats", get(stats))
+        .route("/jobs", post(submit_job))
+        .route("/jobs/:id", get(job).delete(cancel_job))
+        .route("/ws", get(events))
+        .route("/openapi.json", get(openapi))
+        .route("/metrics", get(metrics))
+        .route_layer(middleware::from_fn_with_state(
+            dictionary.clone(),
+            count_requests,
+        ))
+        .with_state(dictionary);
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            fail(Failure::Io, format!("unable to start the server: {}", e));
+        }
+    };
+    let result = runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind(args.bind).await?;
+        info!("listening on {}", args.bind);
+        axum::serve(listener, app).await
+    });
+    if let Err(e) = result {
+        fail(
+            Failure::Io,
+            format!("unable to serve on {}: {}", args.bind, e),
+        );
+    }
+}
@@ -0,0 +1,592 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    mem::size_of,
+    ops::RangeInclusive,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    brute_force, dynamic, heuristic, ida, meet_in_the_middle, progress::Observed, Checkpoint,
+    CheckpointError, CheckpointPolicy, Expression, Limits, NoProgress, Objective, Operation,
+    OperationDictionary, Progress, Semantics, SolverObserver,
+};
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Strategy {
+    BruteForce,
+    Dynamic,
+    // Composes targets out of reach of the exhaustive strategies from the values reachable
+    // within the maximum size, without guaranteeing minimality.
+    Heuristic,
+    // Iterative deepening over the size of a single minimal expression per target.
+    Ida,
+    // Joins the targets from a table of the values reachable with half of the maximum size.
+    MeetInTheMiddle,
+}
+
+// Built by `SolverConfig::builder()`, so that settings can be added without breaking the code
+// using it. The settings can only be read once built, so that they stay as `build()` checked
+// them.
+#[derive(Clone, Debug)]
+pub struct SolverConfig {
+    pub(crate) numbers: Vec<i64>,
+    pub(crate) max_size: usize,
+    pub(crate) operations: OperationDictionary,
+    pub(crate) strategy: Strategy,
+    // Only these values are searched for when not empty.
+    pub(crate) targets: Vec<i64>,
+    pub(crate) semantics: Semantics,
+    pub(crate) objective: Objective,
+    pub(crate) limits: Limits,
+    pub(crate) max_alternatives: Option<usize>,
+    // Values keep the solutions of every larger size searched too, each limited to
+    // `max_alternatives`, which then bounds the memory used. Every size is searched, even once
+    // the targets or the range to cover are solved. Only honoured by the brute-force strategy.
+    pub(crate) all_sizes: bool,
+    // The search stops at the first size after which every value of the range is solved and
+    // cannot get cheaper, even if larger sizes would compose values outside of it.
+    pub(crate) cover: Option<RangeInclusive<i64>>,
+    // The search stops once this fraction of the targets, or of the range to cover, is solved
+    // instead of all of them.
+    pub(crate) coverage: Option<f64>,
+    // Keep a single alternative per class of expressions equivalent up to commutativity and
+    // associativity.
+    pub(crate) dedupe: bool,
+    // Only count the alternatives distinct up to commutativity and associativity, without
+    // keeping them.
+    pub(crate) count_distinct: bool,
+    // Equally minimal alternatives are ordered, and limited, preferring those using the least
+    // preferred operations the fewest times. Operations not listed are the least preferred.
+    pub(crate) preference: Vec<Operation>,
+    // Equally minimal alternatives combining fewer distinct values are ordered, and limited,
+    // first, taking precedence over the preference between operations.
+    pub(crate) prefer_few_sources: bool,
+    // Equally minimal alternatives with fewer operations in series, the most balanced ones, are
+    // ordered, and limited, first, after those combining fewer distinct values.
+    pub(crate) prefer_shallow: bool,
+    // The search stops once it has run this long, keeping what it found in the size it was
+    // exploring.
+    pub(crate) timeout: Option<Duration>,
+    // Cancelling the token stops the search as with the timeout, keeping what it found.
+    pub(crate) cancellation: Option<CancellationToken>,
+    // Approximate number of bytes the search can use. Past it a single alternative is kept per
+    // value, and if that is not enough the search stops as with the timeout.
+    pub(crate) max_memory: Option<usize>,
+    // Only honoured by the brute-force strategy.
+    pub(crate) checkpoint: Option<CheckpointPolicy>,
+}
+
+impl SolverConfig {
+    pub fn numbers(&self) -> &[i64] {
+        &self.numbers
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    pub fn operations(&self) -> &OperationDictionary {
+        &self.operations
+    }
+
+    pub fn strategy(&self) -> Strategy {
+        self.strategy
+    }
+
+    pub fn targets(&self) -> &[i64] {
+        &self.targets
+    }
+
+    pub fn semantics(&self) -> Semantics {
+        self.semantics
+    }
+
+    pub fn objective(&self) -> &Objective {
+        &self.objective
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub fn cover(&self) -> Option<&RangeInclusive<i64>> {
+        self.cover.as_ref()
+    }
+
+    pub fn coverage(&self) -> Option<f64> {
+        self.coverage
+    }
+
+    // When a search starting now must stop.
+    pub(crate) fn deadline(&self) -> Deadline {
+        Deadline {
+            instant: self.timeout.map(|timeout| Instant::now() + timeout),
+            cancellation: self.cancellation.clone(),
+        }
+    }
+
+    // Targets can stop the search once no larger size can produce a cheaper expression for any
+    // of them, or for the fraction of them to cover, as can the values of the range to cover.
+    pub(crate) fn search_complete(&self, solutions: &HashMap<i64, Solution>, size: usize) -> bool {
+        if self.all_sizes {
+            return false;
+        }
+        let minimum_cost = self
+            .objective
+            .minimum_cost(size + 1, &self.operations, &self.numbers);
+        let solved = |value: &i64| {
+            solutions
+                .get(value)
+                .is_some_and(|solution| solution.cost <= minimum_cost)
+        };
+        (!self.targets.is_empty()
+            && self.covers(self.targets.iter().copied(), self.targets.len(), solved))
+            || self.cover.as_ref().is_some_and(|cover| {
+                let total = (cover.end() - cover.start() + 1).max(0) as usize;
+                self.covers(cover.clone(), total, solved)
+            })
+    }
+
+    // Whether all the `total` values, or the fraction of them to cover, are solved.
+    fn covers(
+        &self,
+        mut values: impl Iterator<Item = i64>,
+        total: usize,
+        solved: impl Fn(&i64) -> bool,
+    ) -> bool {
+        match self.coverage {
+            Some(coverage) => values.filter(solved).count() as f64 >= coverage * total as f64,
+            None => values.all(|value| solved(&value)),
+        }
+    }
+
+    // Whether combining operands of these values can be skipped, as no minimal expression does
+    // it: identities like `x*1` give back an operand, which alone is smaller, and `x-x` or `x/x`
+    // give the same value as `n-n` or `n/n` with a number `n`, which is smaller when `x` combines
+    // several numbers, or as a number unless it costs more. Limits on the numbers can forbid
+    // using `n` twice, so they keep the cancellations, and an identity can separate two
+    // operations forbidden to be adjacent, so adjacency constraints keep the identities. Both
+    // drop an operand, which can hold a required number, so nothing is skipped then.
+    pub(crate) fn is_wasteful(
+        &self,
+        operation: Operation,
+        l: i64,
+        r: i64,
+        composite: bool,
+    ) -> bool {
+        if !self.limits.required().is_empty() {
+            return false;
+        }
+        if operation.is_identity(l, r) {
+            return self.limits.forbidden_adjacent().is_empty();
+        }
+        let cancelled = match operation {
+            Operation::SUB if l == r => 0,
+            Operation::DIV if l == r && l != 0 => 1,
+            _ => return false,
+        };
+        let cheaper_number = self.numbers.contains(&cancelled)
+            && self.objective.number_cost(cancelled)
+                <= 2 * self.objective.number_cost(l) + self.objective.cost(operation);
+        (composite || cheaper_number)
+            && self
+                .numbers
+                .iter()
+                .all(|n| self.limits.max_source_uses(*n).is_none())
+    }
+
+    pub(crate) fn target_set(&self) -> HashSet<i64> {
+        self.targets.iter().copied().collect()
+    }
+
+    pub(crate) fn deduplicates(&self) -> bool {
+        self.dedupe || self.count_distinct
+    }
+
+    // Deduplicated and ordered solutions keep all their alternatives until the end of each size,
+    // when they are finished.
+    pub(crate) fn keeps_all_alternatives(&self) -> bool {
+        self.deduplicates() || self.orders()
+    }
+
+    // Whether equally minimal alternatives are ordered by preference.
+    pub(crate) fn orders(&self) -> bool {
+        !self.preference.is_empty() || self.prefer_few_sources || self.prefer_shallow
+    }
+}
+
+// `count` is the number of alternatives found, of which only the first `max_alternatives` are
+// kept in `alternatives`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Solution {
+    pub size: usize,
+    pub cost: u64,
+    pub count: u64,
+    pub alternatives: Vec<Expression>,
+    // With `all_sizes`, the solutions of the value at the other sizes searched, in increasing
+    // size: the larger ones, and with operation costs the smaller but costlier ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub other_sizes: Vec<Solution>,
+}
+
+impl Solution {
+    pub(crate) fn empty(size: usize, cost: u64) -> Solution {
+        Solution {
+            size,
+            cost,
+            count: 0,
+            alternatives: vec![],
+            other_sizes: vec![],
+        }
+    }
+
+    pub(crate) fn push(&mut self, expression: Expression, limit: Option<usize>) {
+        self.count += 1;
+        if limit.is_none_or(|limit| self.alternatives.len() < limit) {
+            self.alternatives.push(expression);
+        }
+    }
+
+    pub(crate) fn merge(&mut self, other: Solution, limit: Option<usize>) {
+        if !self.is_better_than(other.cost, other.size) {
+            *self = other;
+        } else if (self.cost, self.size) == (other.cost, other.size) {
+            self.count += other.count;
+            let available = limit.map_or(usize::MAX, |limit| {
+                limit.saturating_sub(self.alternatives.len())
+            });
+            self.alternatives
+                .extend(other.alternatives.into_iter().take(available));
+        }
+    }
+
+    // Orders the operands of the alternatives as `Expression::order_operands` does, keeping once
+    // the alternatives which then print the same.
+    pub fn order_operands(&mut self, semantics: Semantics) {
+        let mut seen = HashSet::new();
+        self.alternatives = self
+            .alternatives
+            .iter()
+            .map(|alternative| alternative.order_operands(semantics))
+            .filter(|alternative| seen.insert(alternative.clone()))
+            .collect();
+        for other in self.other_sizes.iter_mut() {
+            other.order_operands(semantics);
+        }
+    }
+
+    // Deduplicates and orders the alternatives kept while searching, then applies the limit.
+    // Alternatives are first sorted, so that their order does not depend on how the search
+    // found them, and the preferences reorder them stably.
+    pub(crate) fn finish(&mut self, config: &SolverConfig) {
+        if config.deduplicates() {
+            self.dedupe(config.limits.chains_only());
+        }
+        if config.count_distinct {
+            self.alternatives = vec![];
+            return;
+        }
+        self.alternatives.sort();
+        if config.orders() {
+            self.order(config);
+        }
+        if let Some(limit) = config.max_alternatives {
+            self.alternatives.truncate(limit);
+        }
+    }
+
+    // Replaces the alternatives with their distinct canonical forms, in the order they were
+    // found, and counts them. Chains keep the first alternative of every canonical form instead,
+    // as the canonical form can move a chain to the right of an operation.
+    fn dedupe(&mut self, chains_only: bool) {
+        let mut seen = HashSet::new();
+        self.alternatives = self
+            .alternatives
+            .drain(..)
+            .filter_map(|alternative| {
+                let canonical = alternative.canonical();
+                let kept = if chains_only {
+                    alternative
+                } else {
+                    canonical.clone()
+                };
+                seen.insert(canonical).then_some(kept)
+            })
+            .collect();
+        self.count = self.alternatives.len() as u64;
+    }
+
+    // Sorts the alternatives by their distinct values and by their depth, if requested, then by
+    // their uses of the operations, from the least preferred to the most preferred, keeping their
+    // sorted order between ties.
+    fn order(&mut self, config: &SolverConfig) {
+        self.alternatives.sort_by_cached_key(|alternative| {
+            let sources = if config.prefer_few_sources {
+                alternative.sources()
+            } else {
+                0
+            };
+            let depth = if config.prefer_shallow {
+                alternative.depth()
+            } else {
+                0
+            };
+            let uses: Vec<usize> = config
+                .preference
+                .iter()
+                .rev()
+                .map(|operation| alternative.uses(*operation))
+                .collect();
+            let unlisted = alternative.operations() - uses.iter().sum::<usize>();
+            (sources, depth, unlisted, uses)
+        });
+    }
+
+    // Approximate number of bytes used by the solution and its value in a dictionary, counting
+    // the nodes of a binary tree with `size` leaves for every alternative.
+    pub(crate) fn memory(&self) -> usize {
+        size_of::<(i64, Solution)>()
+            + self.alternatives.len() * (2 * self.size - 1) * size_of::<Expression>()
+            + self.other_sizes.iter().map(Solution::memory).sum::<usize>()
+    }
+
+    // Keeps the first alternative only, and no other size, to save memory.
+    pub(crate) fn degrade(&mut self) {
+        self.alternatives.truncate(1);
+        self.alternatives.shrink_to_fit();
+        self.other_sizes = vec![];
+    }
+
+    // Adds the solution of the value at another size, keeping the better one as the solution
+    // and the other sizes in increasing order.
+    pub(crate) fn add_size(&mut self, mut other: Solution) {
+        if !self.is_better_than(other.cost, other.size) {
+            std::mem::swap(self, &mut other);
+            self.other_sizes = std::mem::take(&mut other.other_sizes);
+        }
+        self.other_sizes.push(other);
+        self.other_sizes.sort_by_key(|other| other.size);
+    }
+
+    pub(crate) fn is_better_than(&self, cost: u64, size: usize) -> bool {
+        (self.cost, self.size) <= (cost, size)
+    }
+}
+
+// Why a search stopped before exploring every size.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Interruption {
+    Timeout,
+    Memory,
+    Cancelled,
+}
+
+// Shared with a running search to stop it from another thread, e.g. when its results are no
+// longer needed. The search checks it along with its timeout, and returns what it found so far.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// The timeout and the cancellation of a running search.
+pub(crate) struct Deadline {
+    instant: Option<Instant>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl Deadline {
+    pub(crate) fn expired(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(|cancellation| cancellation.is_cancelled())
+            || self
+                .instant
+                .is_some_and(|instant| Instant::now() >= instant)
+    }
+
+    // Why a search stopped once expired.
+    pub(crate) fn interruption(&self) -> Interruption {
+        if self
+            .cancellation
+            .as_ref()
+            .is_some_and(|cancellation| cancellation.is_cancelled())
+        {
+            Interruption::Cancelled
+        } else {
+            Interruption::Timeout
+        }
+    }
+}
+
+// Serializable, with the solutions of the values and what stopped the search, so that results
+// can be saved and loaded, or sent to another program, without searching again.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Solutions {
+    solutions: HashMap<i64, Solution>,
+    maximum_composed: i64,
+    overflows: u64,
+    size_searched: usize,
+    truncated: Option<(usize, Interruption)>,
+    degraded: bool,
+    // Why the last checkpoint that failed to be saved was not.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    unsaved_checkpoint: Option<String>,
+}
+
+impl Solutions {
+    pub(crate) fn new(
+        solutions: HashMap<i64, Solution>,
+        maximum_composed: i64,
+        overflows: u64,
+        size_searched: usize,
+        truncated: Option<(usize, Interruption)>,
+        degraded: bool,
+    ) -> Solutions {
+        Solutions {
+            solutions,
+            maximum_composed,
+            overflows,
+            size_searched,
+            truncated,
+            degraded,
+            unsaved_checkpoint: None,
+        }
+    }
+
+    pub(crate) fn with_unsaved_checkpoint(mut self, unsaved: Option<String>) -> Solutions {
+        self.unsaved_checkpoint = unsaved;
+        self
+    }
+
+    pub fn get(&self, value: i64) -> Option<&Solution> {
+        self.solutions.get(&value)
+    }
+
+    // The values found with their solutions, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (i64, &Solution)> {
+        self.solutions
+            .iter()
+            .map(|(value, solution)| (*value, solution))
+    }
+
+    pub fn len(&self) -> usize {
+        self.solutions.len()
+    }
+
+    // Orders the operands of the alternatives of every value, see `Solution::order_operands`.
+    pub fn order_operands(&mut self, semantics: Semantics) {
+        for solution in self.solutions.values_mut() {
+            solution.order_operands(semantics);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.solutions.is_empty()
+    }
+
+    pub fn maximum_composed(&self) -> i64 {
+        self.maximum_composed
+    }
+
+    // Number of candidates discarded because an intermediate value did not fit the width.
+    pub fn overflows(&self) -> u64 {
+        self.overflows
+    }
+
+    // The largest size searched, smaller than `max_size` when the search stopped early.
+    pub fn size_searched(&self) -> usize {
+        self.size_searched
+    }
+
+    // The size the timeout or the memory budget interrupted, if any: its values may be missing
+    // or not minimal, while every smaller size was fully explored.
+    pub fn truncated(&self) -> Option<usize> {
+        self.truncated.map(|(size, _)| size)
+    }
+
+    pub fn interruption(&self) -> Option<Interruption> {
+        self.truncated.map(|(_, interruption)| interruption)
+    }
+
+    // Whether alternatives were dropped to stay within the memory budget, leaving a single one
+    // per value.
+    pub fn degraded(&self) -> bool {
+        self.degraded
+    }
+
+    // Why the checkpoint could not be saved, the last time it failed: the search goes on
+    // without it, and a resumed search starts from an older one.
+    pub fn unsaved_checkpoint(&self) -> Option<&str> {
+        self.unsaved_checkpoint.as_deref()
+    }
+}
+
+pub struct Solver {
+    config: SolverConfig,
+}
+
+impl Solver {
+    pub fn new(config: SolverConfig) -> Solver {
+        Solver { config }
+    }
+
+    pub fn config(&self) -> &SolverConfig {
+        &self.config
+    }
+
+    pub fn solve(&self) -> Solutions {
+        self.solve_with_progress(&NoProgress)
+    }
+
+    pub fn solve_with_progress(&self, progress: &dyn Progress) -> Solutions {
+        match self.config.strategy {
+            Strategy::BruteForce => {
+                brute_force::search(&self.config, progress, Checkpoint::new(&self.config))
+            }
+            Strategy::Dynamic => dynamic::search(&self.config, progress),
+            Strategy::Heuristic => heuristic::search(&self.config, progress),
+            Strategy::Ida => ida::search(&self.config, progress),
+            Strategy::MeetInTheMiddle => meet_in_the_middle::search(&self.config, progress),
+        }
+    }
+
+    // Searches, calling the hooks of the observer as the search goes.
+    pub fn solve_with_observer(&self, observer: &dyn SolverObserver) -> Solutions {
+        self.solve_with_progress(&Observed::new(observer))
+    }
+
+    pub fn resume(&self, checkpoint: Checkpoint) -> Result<Solutions, CheckpointError> {
+        self.resume_with_progress(checkpoint, &NoProgress)
+    }
+
+    pub fn resume_with_progress(
+        &self,
+        checkpoint: Checkpoint,
+        progress: &dyn Progress,
+    ) -> Result<Solutions, CheckpointError> {
+        if self.config.strategy != Strategy::BruteForce {
+            return Err(CheckpointError::UnsupportedStrategy);
+        }
+        if !checkpoint.matches(&self.config) {
+            return Err(CheckpointError::ConfigMismatch);
+        }
+        Ok(brute_force::search(&self.config, progress, checkpoint))
+    }
+}
@@ -0,0 +1,136 @@
+use beltmatic_calc::{Solution, Solutions, Solver, SolverConfig, Step};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::{
+    chart, configure_threads, exit_truncated,
+    failure::{fail, Failure},
+    progress, report_covered, solver_config, warn_truncated, write_atomically, StatsArgs,
+};
+
+// Columns of the bar of the size solving the most values in the histogram.
+const BAR: usize = 40;
+
+// The values found that every solver searches: the positive ones and the targets. Brute force
+// keeps the other values it composes too, which would tell the solvers apart.
+pub(crate) fn searched<'a>(
+    config: &'a SolverConfig,
+    solutions: &'a Solutions,
+) -> impl Iterator<Item = (i64, &'a Solution)> + 'a {
+    solutions
+        .iter()
+        .filter(|(value, _)| *value > 0 || config.targets().contains(value))
+}
+
+// A summary of the values found: how many of the values from 1 to the largest value composed
+// are solved, how many values are solved with each size, and the machines of each operation
+// used by the first alternatives of all the values.
+#[derive(Serialize)]
+pub(crate) struct Statistics {
+    values: usize,
+    largest: i64,
+    covered: usize,
+    sizes: BTreeMap<usize, usize>,
+    uses: BTreeMap<String, usize>,
+    overflows: u64,
+}
+
+impl Statistics {
+    pub(crate) fn new(config: &SolverConfig, solutions: &Solutions) -> Statistics {
+        let largest = solutions.maximum_composed();
+        let mut sizes: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut uses: BTreeMap<String, usize> = config
+            .operations()
+            .symbols()
+            .into_iter()
+            .map(|symbol| (symbol, 0))
+            .collect();
+        let mut values = 0;
+        let mut covered = 0;
+        for (value, solution) in searched(config, solutions) {
+            values += 1;
+            *sizes.entry(solution.size).or_default() += 1;
+            if (1..=largest).contains(&value) {
+                covered += 1;
+            }
+            let steps = solution
+                .alternatives
+                .first()
+                .and_then(|alternative| alternative.steps(config.semantics()).ok())
+                .unwrap_or_default();
+            for step in steps {
+                let operation = match step {
+                    Step::Binary { operation, .. } => operation.to_string(),
+                    Step::Unary { operation, .. } => operation.to_string(),
+                };
+                *uses.entry(operation).or_default() += 1;
+            }
+        }
+        Statistics {
+            values,
+            largest,
+            covered,
+            sizes,
+            uses,
+            overflows: solutions.overflows(),
+        }
+    }
+}
+
+// The number of values solved with each size, aligned and followed by a bar of `#` as long as
+// the number relative to the largest one, every size solving values having at least one.
+fn histogram(sizes: &BTreeMap<usize, usize>) -> Vec<String> {
+    let largest = sizes.values().copied().max().unwrap_or(0);
+    let size_width = sizes.keys().last().map_or(1, |size| size.to_string().len());
+    let values_width = largest.to_string().len();
+    sizes
+        .iter()
+        .map(|(size, values)| {
+            let bar = (values * BAR).div_ceil(largest.max(1));
+            format!(
+                "size {:>size_width$}: {:>values_width$} values {}",
+                size,
+                values,
+                "#".repeat(bar)
+            )
+        })
+        .collect()
+}
+
+pub(crate) fn run(stats: &StatsArgs) {
+    let args = &stats.search;
+    if let Some(Err(e)) = stats.chart.as_deref().map(chart::check) {
+        fail(Failure::Arguments, e);
+    }
+    configure_threads(args.threads);
+    let solver = Solver::new(solver_config(args));
+    let config = solver.config();
+    let solutions = solver.solve_with_progress(progress(args).as_ref());
+    warn_truncated(&solutions);
+    report_covered(args, config, &solutions);
+    let statistics = Statistics::new(config, &solutions);
+    println!("values: {}", statistics.values);
+    println!("largest value: {}", statistics.largest);
+    println!(
+        "covered: {} of 1..{} ({:.1}%)",
+        statistics.covered,
+        statistics.largest,
+        100.0 * statistics.covered as f64 / statistics.largest.max(1) as f64
+    );
+    for line in histogram(&statistics.sizes) {
+        println!("{}", line);
+    }
+    for symbol in config.operations().symbols() {
+        println!("uses of {}: {}", symbol, statistics.uses[&symbol]);
+    }
+    println!("overflows: {}", statistics.overflows);
+    if let Some(path) = &stats.chart {
+        if let Err(e) = write_atomically(path, |out| chart::write(out, path, config, &solutions)) {
+            fail(
+                Failure::Io,
+                format!("unable to write {}: {}", path.display(), e),
+            );
+        }
+    }
+    exit_truncated(&solutions);
+}
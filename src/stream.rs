@@ -0,0 +1,33 @@
+use futures_core::Stream;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{lazy::Receiver, Solution, Solver};
+
+// The solutions of a search running on its own thread, yielded as soon as they are known, in
+// the order the search reports them as solved. At most `capacity` of them wait to be consumed:
+// past that the search blocks until the consumer catches up. Dropping the stream cancels the
+// search, which stops delivering solutions.
+pub struct SolutionStream {
+    receiver: Receiver,
+}
+
+impl Solver {
+    // Searches on a new thread, streaming the solutions found. Async runtimes can consume them
+    // without blocking a thread of theirs on the whole search.
+    pub fn solve_stream(self, capacity: usize) -> SolutionStream {
+        SolutionStream {
+            receiver: Receiver::spawn(self.config(), capacity),
+        }
+    }
+}
+
+impl Stream for SolutionStream {
+    type Item = (i64, Solution);
+
+    fn poll_next(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next(context.waker())
+    }
+}
@@ -0,0 +1,168 @@
+use beltmatic_calc::{Expression, Semantics, Solution};
+
+use crate::{difficulty, group, machines, minimal, suffix, Annotations, Syntax};
+
+const FIELDS: [&str; 9] = [
+    "value",
+    "size",
+    "cost",
+    "count",
+    "expr",
+    "alternatives",
+    "machines",
+    "difficulty",
+    "minimal",
+];
+
+// A line printed per value in place of the text format, e.g. `{value}\t{size}\t{expr}`. Fields
+// are named between braces, `{{` and `}}` are literal braces, and `\t`, `\n` and `\\` are
+// escapes, so the line needs no quoting tricks in the shell.
+pub(crate) struct Template {
+    parts: Vec<Part>,
+}
+
+enum Part {
+    Text(String),
+    Field(Field),
+}
+
+enum Field {
+    Value,
+    Size,
+    Cost,
+    Count,
+    // The first alternative.
+    Expr,
+    // Every alternative, separated by `, `.
+    Alternatives,
+    // The machines and extractors of the first alternative.
+    Machines,
+    Difficulty,
+    Minimal,
+}
+
+impl Template {
+    pub(crate) fn parse(template: &str) -> Result<Template, String> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed {{ in template {}", template)),
+                        }
+                    }
+                    let field = match name.as_str() {
+                        "value" => Field::Value,
+                        "size" => Field::Size,
+                        "cost" => Field::Cost,
+                        "count" => Field::Count,
+                        "expr" => Field::Expr,
+                        "alternatives" => Field::Alternatives,
+                        "machines" => Field::Machines,
+                        "difficulty" => Field::Difficulty,
+                        "minimal" => Field::Minimal,
+                        _ => {
+                            return Err(format!(
+                                "unknown field {{{}}} in template, allowed={:?}",
+                                name, FIELDS
+                            ))
+                        }
+                    };
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                '}' => return Err(format!("unmatched }} in template {}", template)),
+                '\\' => match chars.peek() {
+                    Some('t') => {
+                        chars.next();
+                        text.push('\t');
+                    }
+                    Some('n') => {
+                        chars.next();
+                        text.push('\n');
+                    }
+                    Some('\\') => {
+                        chars.next();
+                        text.push('\\');
+                    }
+                    _ => text.push('\\'),
+                },
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template { parts })
+    }
+
+    // The line of the value, the fields of unsolved values, and of annotations not requested,
+    // being empty.
+    pub(crate) fn render(
+        &self,
+        value: i64,
+        solution: Option<&Solution>,
+        syntax: Syntax,
+        semantics: Semantics,
+        annotations: &Annotations,
+    ) -> String {
+        let first = solution.and_then(|solution| solution.alternatives.first());
+        let render = |expression: &Expression| annotations.render(syntax, expression, semantics);
+        let mut line = String::new();
+        for part in &self.parts {
+            let field = match part {
+                Part::Text(text) => {
+                    line.push_str(text);
+                    continue;
+                }
+                Part::Field(field) => field,
+            };
+            let text = match field {
+                Field::Value => Some(value.to_string()),
+                Field::Size => solution.map(|solution| solution.size.to_string()),
+                Field::Cost => solution.map(|solution| solution.cost.to_string()),
+                Field::Count => Some(solution.map_or(0, |solution| solution.count).to_string()),
+                Field::Expr => first.map(render),
+                Field::Alternatives => solution.map(|solution| {
+                    let alternatives: Vec<String> = if annotations.group {
+                        group(&solution.alternatives)
+                            .into_iter()
+                            .map(|(expression, variants)| {
+                                format!("{}{}", render(expression), suffix(variants))
+                            })
+                            .collect()
+                    } else {
+                        solution.alternatives.iter().map(render).collect()
+                    };
+                    alternatives.join(", ")
+                }),
+                Field::Machines => first.map(machines),
+                Field::Difficulty => difficulty(annotations, value, solution)
+                    .map(|difficulty| difficulty.to_string()),
+                Field::Minimal => solution
+                    .and_then(|solution| {
+                        minimal(annotations.proof.as_ref(), value, solution, semantics)
+                    })
+                    .map(|minimal| minimal.to_string()),
+            };
+            line.push_str(&text.unwrap_or_default());
+        }
+        line
+    }
+}
@@ -0,0 +1,59 @@
+use beltmatic_calc::{Expression, Rates};
+
+use crate::{
+    failure::{fail, Failure},
+    parse_per_operation, ThroughputArgs,
+};
+
+// The rates given by the arguments, exiting if they are not positive numbers.
+pub(crate) fn rates(extractor_rate: f64, op_rate: &Option<String>) -> Rates {
+    if extractor_rate.is_nan() || extractor_rate <= 0.0 {
+        fail(
+            Failure::Arguments,
+            format!("extractor_rate must be > 0, was {}", extractor_rate),
+        );
+    }
+    let (rates, unary_rates) = match op_rate {
+        Some(op_rates) => match parse_per_operation::<f64>(op_rates) {
+            Some((rates, unary_rates))
+                if rates
+                    .values()
+                    .chain(unary_rates.values())
+                    .all(|rate| *rate > 0.0) =>
+            {
+                (rates, unary_rates)
+            }
+            _ => {
+                fail(
+                    Failure::Arguments,
+                    format!(
+                        "op_rate must be a CSV list of <operation>:<rate> with rates > 0, was {}",
+                        op_rates
+                    ),
+                );
+            }
+        },
+        None => Default::default(),
+    };
+    Rates::new(extractor_rate, rates, unary_rates)
+}
+
+pub(crate) fn run(args: &ThroughputArgs) {
+    let rates = rates(args.extractor_rate, &args.op_rate);
+    for text in args.expressions.iter() {
+        let expression: Expression = match text.parse() {
+            Ok(expression) => expression,
+            Err(e) => {
+                fail(
+                    Failure::Arguments,
+                    format!("unable to parse {}: {}", text, e),
+                );
+            }
+        };
+        println!(
+            "{} -> {} items/s",
+            expression,
+            rates.throughput(&expression)
+        );
+    }
+}
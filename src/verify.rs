@@ -0,0 +1,73 @@
+use beltmatic_calc::{EvalError, Expression, Semantics};
+
+use crate::{
+    failure::{fail, Failure},
+    objective, semantics, VerifyArgs,
+};
+
+// The innermost sub-expression whose evaluation fails, with its error.
+fn failure(expression: &Expression, semantics: Semantics) -> Option<(&Expression, EvalError)> {
+    match expression {
+        Expression::Number(_) => None,
+        Expression::Binary(operation, left, right) => {
+            let l = match left.eval(semantics) {
+                Ok(l) => l,
+                Err(_) => return failure(left, semantics),
+            };
+            let r = match right.eval(semantics) {
+                Ok(r) => r,
+                Err(_) => return failure(right, semantics),
+            };
+            operation
+                .apply(l, r, semantics)
+                .err()
+                .map(|e| (expression, e))
+        }
+        Expression::Unary(operation, operand) => match operand.eval(semantics) {
+            Ok(v) => operation.apply(v, semantics).err().map(|e| (expression, e)),
+            Err(_) => failure(operand, semantics),
+        },
+    }
+}
+
+pub(crate) fn run(args: &VerifyArgs) {
+    let expression: Expression = match args.expression.parse() {
+        Ok(expression) => expression,
+        Err(e) => {
+            fail(
+                Failure::Arguments,
+                format!("unable to parse {}: {}", args.expression, e),
+            );
+        }
+    };
+    let semantics = semantics(
+        args.width,
+        args.allow_truncating_division,
+        args.max_intermediate,
+        args.no_negative_intermediates,
+    );
+    let objective = objective(&args.op_cost, &args.cost_model);
+    println!("expression: {}", expression);
+    println!("operations: {}", expression.operations());
+    if objective.is_weighted() {
+        println!("cost: {}", objective.expression_cost(&expression));
+    }
+    let value = match expression.eval(semantics) {
+        Ok(value) => value,
+        Err(e) => {
+            let message = match failure(&expression, semantics) {
+                Some((failing, e)) => format!("error: {} in {}", e, failing),
+                None => format!("error: {}", e),
+            };
+            fail(Failure::Check, message);
+        }
+    };
+    println!("value: {}", value);
+    if let Some(target) = args.target {
+        if value == target {
+            println!("target: {} matched", target);
+        } else {
+            fail(Failure::Check, format!("target: {} not matched", target));
+        }
+    }
+}
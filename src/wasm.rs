@@ -0,0 +1,10 @@
+use wasm_bindgen::prelude::*;
+
+use crate::embedded;
+
+// Solves `target` combining the numbers in `1..max_number` with the CSV list of operations
+// `ops`, returning the solution as a JSON document with the same fields as the CLI output.
+#[wasm_bindgen]
+pub fn solve(target: i32, max_number: u32, ops: &str) -> Result<String, JsError> {
+    embedded::solve(target as i64, max_number, ops).map_err(|e| JsError::new(&e))
+}